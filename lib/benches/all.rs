@@ -2,7 +2,13 @@
 
 use chrono::Duration;
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use unit_parser::prelude::*;
+use pest::Parser;
+use pest_derive::Parser;
+use std::{borrow::Cow, fs, path::PathBuf};
+use unit_parser::{
+    internal::{join_continuation, Lexer, Token, UnitParser},
+    prelude::*,
+};
 
 pub fn criterion_benchmark(c: &mut Criterion) {
     c.bench_function("load example", |b| {
@@ -41,6 +47,95 @@ pub fn criterion_benchmark(c: &mut Criterion) {
             .unwrap();
         })
     });
+
+    // both lexers split the corpus into sections and entries and join continued values, the parser
+    // also resolves specifiers
+    let corpus = read_corpus();
+    c.bench_function("lex corpus", |b| b.iter(|| lex_corpus(black_box(&corpus))))
+        .bench_function("lex corpus with pest", |b| {
+            b.iter(|| lex_corpus_with_pest(black_box(&corpus)))
+        })
+        .bench_function("parse corpus", |b| {
+            b.iter(|| parse_corpus(black_box(&corpus)))
+        });
+}
+
+// real units shipped by systemd and distributions
+fn read_corpus() -> Vec<(PathBuf, String)> {
+    let mut result: Vec<(PathBuf, String)> = fs::read_dir("../lib/benches/corpus")
+        .unwrap()
+        .map(|x| {
+            let path = x.unwrap().path();
+            let content = fs::read_to_string(&path).unwrap();
+            (path, content)
+        })
+        .collect();
+    result.sort();
+    result
+}
+
+fn lex_corpus(corpus: &[(PathBuf, String)]) {
+    for (_, content) in corpus {
+        for token in Lexer::new(content) {
+            match token.unwrap() {
+                Token::Section(name) => {
+                    black_box(name);
+                }
+                Token::Entry {
+                    key,
+                    value,
+                    continued,
+                } => {
+                    let value = match continued {
+                        true => Cow::Owned(join_continuation(value)),
+                        false => Cow::Borrowed(value),
+                    };
+                    black_box((key, value));
+                }
+                Token::Include { .. } => unreachable!(),
+            }
+        }
+    }
+}
+
+fn parse_corpus(corpus: &[(PathBuf, String)]) {
+    for (path, content) in corpus {
+        let filename = path.file_name().unwrap().to_str().unwrap();
+        let parser = UnitParser::new(content, false, filename, path).unwrap();
+        for section in parser {
+            for entry in section.unwrap() {
+                black_box(entry.unwrap());
+            }
+        }
+    }
+}
+
+// the pest grammar used before the hand-written lexer, kept for comparison
+#[derive(Parser)]
+#[grammar = "../benches/unit.pest"]
+struct PestUnitParser;
+
+fn lex_corpus_with_pest(corpus: &[(PathBuf, String)]) {
+    for (_, content) in corpus {
+        let mut parse = PestUnitParser::parse(Rule::unit_file, content).unwrap();
+        for section in parse.next().unwrap().into_inner() {
+            if section.as_rule() == Rule::EOI {
+                break;
+            }
+            let mut section = section.into_inner();
+            black_box(section.next().unwrap().as_str());
+            for entry in section {
+                let mut entry = entry.into_inner();
+                let key = entry.next().unwrap().as_str();
+                let value = entry.next().unwrap().as_str();
+                let value = match value.contains("\\\n") {
+                    true => Cow::Owned(value.replace("\\\n", " ")),
+                    false => Cow::Borrowed(value),
+                };
+                black_box((key, value));
+            }
+        }
+    }
 }
 
 #[derive(UnitConfig, Debug)]
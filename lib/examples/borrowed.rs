@@ -0,0 +1,39 @@
+#![allow(non_snake_case, dead_code)]
+
+use std::borrow::Cow;
+use unit_parser::prelude::*;
+
+#[derive(UnitConfig, Debug)]
+#[unit(suffix = "unit")]
+struct Unit<'a> {
+    #[section(must)]
+    Section: Section<'a>,
+}
+
+#[derive(UnitSection, Debug)]
+struct Section<'a> {
+    /// borrowed from the file buffer
+    #[entry(must)]
+    Plain: &'a str,
+
+    /// borrowed when possible, owned when specifiers were resolved
+    #[entry(must)]
+    Resolved: Cow<'a, str>,
+
+    #[entry(default = "default")]
+    Defaulted: Cow<'a, str>,
+
+    /// words that needed no unquoting are borrowed
+    #[entry(multiple)]
+    Multiple: Vec<Cow<'a, str>>,
+
+    #[entry(multiple)]
+    Words: Vec<&'a str>,
+}
+
+fn main() {
+    let files = UnitFiles::read_named(vec!["lib/examples"], "borrowed", "unit").unwrap();
+    let result = Unit::load_files(&files, false).unwrap();
+
+    println!("result: {:#?}", result);
+}
@@ -0,0 +1,31 @@
+#![allow(non_snake_case, dead_code)]
+
+use unit_parser::prelude::*;
+
+#[derive(UnitConfig, Debug, Clone)]
+#[unit(suffix = "unit")]
+struct Unit {
+    #[section(must)]
+    Section: Section,
+}
+
+#[derive(UnitSection, Debug, Clone)]
+struct Section {
+    #[entry(multiple, subdir = "wants")]
+    Wants: Vec<String>,
+}
+
+fn main() {
+    let mut cache = UnitCache::new();
+
+    // parsed from disk
+    let first =
+        Unit::load_named_cached(vec!["lib/examples/subdir"], "subdir", false, &mut cache).unwrap();
+    // returned from the cache, as nothing changed in between
+    let second =
+        Unit::load_named_cached(vec!["lib/examples/subdir"], "subdir", false, &mut cache).unwrap();
+
+    println!("first: {:#?}", first);
+    println!("second: {:#?}", second);
+    println!("cached units: {}", cache.len());
+}
@@ -0,0 +1,142 @@
+#![allow(non_snake_case, dead_code)]
+
+use std::path::PathBuf;
+use unit_parser::prelude::*;
+
+#[derive(UnitConfig, Debug)]
+#[unit(suffix = "service")]
+struct Unit {
+    #[section(must)]
+    Service: Service,
+}
+
+#[derive(UnitSection, Debug)]
+struct Service {
+    Restart: Option<Restart>,
+    KillMode: Option<KillMode>,
+    Type: Option<ServiceType>,
+    StandardOutput: Option<StandardOutput>,
+    StandardError: Option<StandardOutput>,
+    Nice: Option<NiceLevel>,
+    LimitNOFILE: Option<RLimit>,
+    #[entry(multiple)]
+    IOReadBandwidthMax: Vec<Bandwidth>,
+    #[entry(parse_with = parse_umask)]
+    UMask: Option<u32>,
+    #[entry(validate = check_weight)]
+    CPUWeight: Option<u64>,
+    // renamed in systemd 231, the old name still works
+    #[entry(deprecated = "MemoryLimit")]
+    MemoryMax: Option<Limit>,
+    #[entry(alias = "StartLimitInterval")]
+    StartLimitIntervalSec: Option<TimeSpan>,
+}
+
+// file modes are octal
+fn parse_umask(input: &str) -> Result<u32, std::num::ParseIntError> {
+    u32::from_str_radix(input, 8)
+}
+
+fn check_weight(weight: &u64) -> Result<(), String> {
+    match (1..=10000).contains(weight) {
+        true => Ok(()),
+        false => Err(format!("CPU weight {} is out of range.", weight)),
+    }
+}
+
+// variants are matched as `no`, `on-success`, `on-failure` and so on
+#[derive(UnitEntry, Debug, PartialEq)]
+#[entry_value(rename_all = "kebab-case")]
+enum Restart {
+    No,
+    Always,
+    OnSuccess,
+    OnFailure,
+    OnAbnormal,
+    OnAbort,
+    #[entry_value(alias = "watchdog")]
+    OnWatchdog,
+}
+
+#[derive(UnitEntry, Debug, PartialEq)]
+#[entry_value(rename_all = "lowercase", case_insensitive)]
+enum KillMode {
+    ControlGroup,
+    Mixed,
+    Process,
+    None,
+}
+
+#[derive(UnitEntry, Debug, PartialEq)]
+#[entry_value(case_insensitive)]
+enum ServiceType {
+    #[entry_value(rename = "simple")]
+    Simple,
+    #[entry_value(rename = "exec")]
+    Exec,
+    #[entry_value(rename = "forking")]
+    Forking,
+    #[entry_value(rename = "oneshot", alias = "one-shot")]
+    OneShot,
+}
+
+#[derive(UnitEntry, Debug, PartialEq)]
+#[entry_value(rename_all = "kebab-case")]
+enum StandardOutput {
+    Inherit,
+    Null,
+    Tty,
+    Journal,
+    #[entry_value(rename = "journal+console")]
+    JournalConsole,
+    #[entry_value(prefix = "file:")]
+    File(PathBuf),
+    #[entry_value(prefix = "append:")]
+    Append(PathBuf),
+    #[entry_value(prefix = "fd:")]
+    Fd(String),
+    // unknown values are kept as they are
+    #[entry_value(other)]
+    Other(String),
+}
+
+// delegates to `i8`, with the range checked afterwards
+#[derive(UnitEntry, Debug, PartialEq)]
+#[entry_value(validate = check_nice)]
+struct NiceLevel(i8);
+
+fn check_nice(value: &i8) -> Result<(), String> {
+    match (-20..=19).contains(value) {
+        true => Ok(()),
+        false => Err(format!("Nice level {} is out of range.", value)),
+    }
+}
+
+// `soft:hard`, or a single value for both
+#[derive(UnitEntry, Debug, PartialEq)]
+#[entry_value(delimiter = ":", display)]
+struct RLimit {
+    soft: Limit,
+    hard: Option<Limit>,
+}
+
+#[derive(UnitEntry, Debug, PartialEq)]
+#[entry_value(delimiter = " ", display)]
+struct Bandwidth {
+    device: PathBuf,
+    bandwidth: ByteSize,
+}
+
+fn main() {
+    let result = Unit::load_named(vec!["lib/examples/entries"], "entries", false).unwrap();
+    println!("{:#?}", result);
+    if let Some(limit) = &result.Service.LimitNOFILE {
+        println!("LimitNOFILE={}", limit);
+    }
+    for bandwidth in result.Service.IOReadBandwidthMax.iter() {
+        println!("IOReadBandwidthMax={}", bandwidth);
+    }
+    // errors list the accepted values, or tell what went wrong in a field
+    println!("{}", Restart::parse_from_str("sometimes").unwrap_err());
+    println!("{}", NiceLevel::parse_from_str("25").unwrap_err());
+}
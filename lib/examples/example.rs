@@ -62,6 +62,14 @@ pub struct AdvancedSection {
     #[entry(multiple)]
     Multiple: Vec<i64>,
 
+    /// a field that can appear multiple times, with quoted values
+    #[entry(multiple)]
+    Quoted: Vec<String>,
+
+    /// a field that can appear multiple times, separated by `;`
+    #[entry(multiple, separator = ";")]
+    Separated: Vec<String>,
+
     /// an optional field
     Optional: Option<u64>,
 }
@@ -0,0 +1,33 @@
+#![allow(non_snake_case, dead_code)]
+
+use std::collections::HashMap;
+use unit_parser::prelude::*;
+
+#[derive(UnitConfig, Debug)]
+#[unit(suffix = "service")]
+struct Unit {
+    #[section(must)]
+    Service: Service,
+}
+
+#[derive(UnitSection, Debug)]
+struct Service {
+    #[entry(multiple)]
+    ExecStartPre: Vec<ExecCommand>,
+
+    #[entry(multiple)]
+    ExecStart: Vec<ExecCommand>,
+}
+
+fn main() {
+    let result = Unit::load_named(vec!["lib/examples/exec"], "exec", false).unwrap();
+    println!("result: {:#?}", result);
+
+    let env = HashMap::from([
+        ("CONFIG".to_string(), "/etc/my daemon.conf".to_string()),
+        ("EXTRA".to_string(), "--verbose --debug".to_string()),
+    ]);
+    for command in result.Service.ExecStart.iter() {
+        println!("expanded: {:?}", command.expand(&env).argv);
+    }
+}
@@ -0,0 +1,64 @@
+#![allow(non_snake_case, dead_code)]
+
+use unit_parser::prelude::*;
+
+#[derive(UnitConfig, Debug)]
+#[unit(suffix = "service")]
+struct ServiceUnit {
+    #[section(must)]
+    Service: Service,
+}
+
+#[derive(UnitConfig, Debug)]
+#[unit(suffix = "socket")]
+struct SocketUnit {
+    #[section(must)]
+    Socket: Socket,
+}
+
+#[derive(UnitSection, Debug)]
+struct Service {
+    #[entry(multiple)]
+    ExecStart: Vec<ExecCommand>,
+
+    #[entry(flatten)]
+    Exec: ExecContext,
+
+    #[entry(flatten)]
+    Kill: KillContext,
+}
+
+#[derive(UnitSection, Debug)]
+struct Socket {
+    #[entry(multiple)]
+    ListenStream: Vec<String>,
+
+    #[entry(flatten)]
+    Exec: ExecContext,
+}
+
+// shared by services, sockets, mounts and swaps
+#[derive(UnitSection, Debug)]
+struct ExecContext {
+    WorkingDirectory: Option<String>,
+    User: Option<String>,
+    #[entry(default = 0o022)]
+    UMask: u32,
+    #[entry(multiple)]
+    Environment: Vec<EnvironmentAssignments>,
+}
+
+#[derive(UnitSection, Debug)]
+struct KillContext {
+    KillSignal: Option<String>,
+    #[entry(default = true)]
+    SendSIGKILL: bool,
+}
+
+fn main() {
+    let service = ServiceUnit::load_named(vec!["lib/examples/flatten"], "flatten", false).unwrap();
+    println!("service: {:#?}", service);
+
+    let socket = SocketUnit::load_named(vec!["lib/examples/flatten"], "flatten", false).unwrap();
+    println!("socket: {:#?}", socket);
+}
@@ -0,0 +1,43 @@
+#![allow(non_snake_case, dead_code)]
+
+use std::net::IpAddr;
+use unit_parser::prelude::*;
+
+#[derive(UnitConfig, Debug)]
+#[unit(suffix = "network")]
+struct Network {
+    #[section(must)]
+    Match: Match,
+
+    // each `[Address]` and `[Route]` section is a separate item, in order
+    #[section(multiple)]
+    Address: Vec<Address>,
+
+    #[section(multiple)]
+    Route: Vec<Route>,
+}
+
+#[derive(UnitSection, Debug)]
+struct Match {
+    Name: Option<String>,
+}
+
+#[derive(UnitSection, Debug)]
+struct Address {
+    #[entry(must)]
+    Address: String,
+    Label: Option<String>,
+}
+
+#[derive(UnitSection, Debug)]
+struct Route {
+    Destination: Option<String>,
+    Gateway: Option<IpAddr>,
+    #[entry(default = 1024)]
+    Metric: u32,
+}
+
+fn main() {
+    let result = Network::load_named(vec!["lib/examples/network"], "wired", false).unwrap();
+    println!("result: {:#?}", result);
+}
@@ -20,7 +20,7 @@ struct Section {
 
 fn main() {
     let result =
-        Unit::load_named(vec!["lib/examples/templates"], "myunit@template", false).unwrap();
+        Unit::load_named(vec!["lib/examples/templates"], "template@myunit", false).unwrap();
 
     println!("result: {:#?}", result);
 }
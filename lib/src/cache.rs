@@ -0,0 +1,322 @@
+use crate::{
+    config::{dropin_dir_names, unit_file_names, Result, UnitConfig, UnitFiles},
+    fs::{StdFs, UnitFs},
+};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::SystemTime,
+};
+
+// (path, (mtime, size)) of every file and directory that contributes to a unit, including the ones that
+// do not exist (yet)
+type Fingerprint = Vec<(PathBuf, Option<(SystemTime, u64)>)>;
+
+/// A cache of parsed units, keyed by search paths, unit name and `root`.
+///
+/// A cached unit is returned as long as none of the files it was loaded from changed, i.e. the unit
+/// file itself (in every search path), its drop-in directories and files, the files they `.include`,
+/// and its `subdir` directories (such as `.wants`) have the same modification time and size.
+pub struct UnitCache<T> {
+    fs: Rc<dyn UnitFs>,
+    entries: HashMap<(Vec<PathBuf>, String, bool), CacheEntry<T>>,
+}
+
+struct CacheEntry<T> {
+    fingerprint: Fingerprint,
+    // files pulled in by `.include`, which are only known after the unit was read
+    includes: Vec<PathBuf>,
+    value: T,
+}
+
+impl<T> Default for UnitCache<T> {
+    fn default() -> Self {
+        Self::with_fs(Rc::new(StdFs))
+    }
+}
+
+impl<T> UnitCache<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a cache of units loaded from `fs` instead of the disk.
+    pub fn with_fs(fs: Rc<dyn UnitFs>) -> Self {
+        Self {
+            fs,
+            entries: HashMap::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+pub(crate) fn load_named_cached<T>(
+    paths: Vec<PathBuf>,
+    name: &str,
+    root: bool,
+    cache: &mut UnitCache<T>,
+) -> Result<T>
+where
+    T: for<'a> UnitConfig<'a> + Clone,
+{
+    let suffix = <T as UnitConfig>::SUFFIX;
+    let (fullname, actual_file_name) = unit_file_names(name, suffix)?;
+    let subdirs = <T as UnitConfig>::__subdirs();
+    let current = |includes: &[PathBuf]| {
+        fingerprint(
+            cache.fs.as_ref(),
+            &paths,
+            &fullname,
+            &actual_file_name,
+            suffix,
+            &subdirs,
+            includes,
+        )
+    };
+
+    let key = (paths.clone(), fullname.clone(), root);
+    if let Some(entry) = cache.entries.get(&key) {
+        if entry.fingerprint == current(&entry.includes) {
+            return Ok(entry.value.clone());
+        }
+    }
+
+    // taken before reading, so that changes made while loading are noticed next time
+    let mut fingerprint = current(&[]);
+    let mut files = UnitFiles::read_named_from(Rc::clone(&cache.fs), paths, &fullname, suffix)?;
+    if <T as UnitConfig>::INCLUDE {
+        files = files.inline_includes()?;
+    }
+    let value = T::load_files(&files, root)?;
+    for include in files.includes.iter() {
+        fingerprint.push(stat(cache.fs.as_ref(), include));
+    }
+
+    cache.entries.insert(
+        key,
+        CacheEntry {
+            fingerprint,
+            includes: files.includes,
+            value: value.clone(),
+        },
+    );
+    Ok(value)
+}
+
+fn fingerprint(
+    fs: &dyn UnitFs,
+    paths: &[PathBuf],
+    fullname: &str,
+    actual_file_name: &str,
+    suffix: &str,
+    subdirs: &[&str],
+    includes: &[PathBuf],
+) -> Fingerprint {
+    let mut result = Vec::new();
+    let mut push = |path: PathBuf| result.push(stat(fs, &path));
+
+    for dir in paths.iter() {
+        push(dir.join(actual_file_name));
+    }
+
+    for dir_name in dropin_dir_names(fullname, suffix).iter() {
+        for dir in paths.iter() {
+            let path = dir.join(dir_name);
+            let mut confs = conf_files(fs, &path);
+            push(path);
+            // `read_dir` yields entries in no particular order
+            confs.sort();
+            for conf in confs {
+                push(conf);
+            }
+        }
+    }
+
+    // instances also collect the subdirs of their templates
+    let mut names = vec![fullname];
+    if actual_file_name != fullname {
+        names.push(actual_file_name);
+    }
+    for subdir in subdirs.iter() {
+        for dir in paths.iter() {
+            for name in names.iter() {
+                push(dir.join(format!("{}.{}", name, subdir)));
+            }
+        }
+    }
+
+    for include in includes.iter() {
+        push(include.to_owned());
+    }
+
+    result
+}
+
+fn stat(fs: &dyn UnitFs, path: &Path) -> (PathBuf, Option<(SystemTime, u64)>) {
+    let stat = fs.metadata(path).ok().map(|x| (x.modified, x.len));
+    (path.to_owned(), stat)
+}
+
+fn conf_files(fs: &dyn UnitFs, path: &Path) -> Vec<PathBuf> {
+    fs.read_dir(path).map_or(Vec::new(), |entries| {
+        entries
+            .into_iter()
+            .filter(|x| x.extension().is_some_and(|x| x == "conf"))
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        cache::{fingerprint, UnitCache},
+        config::UnitLoad,
+        fs::{FileMetadata, MemoryFs, UnitFs},
+    };
+    use std::{
+        cell::RefCell,
+        io,
+        path::{Path, PathBuf},
+        rc::Rc,
+    };
+    use unit_parser_macro::{UnitConfig, UnitSection};
+
+    #[derive(UnitConfig, Debug, Clone, PartialEq)]
+    #[unit(suffix = "service", include)]
+    struct Unit {
+        #[section(key = "Unit", must)]
+        unit: Section,
+    }
+
+    #[derive(UnitSection, Debug, Clone, PartialEq)]
+    struct Section {
+        #[entry(key = "Description")]
+        description: Option<String>,
+        #[entry(key = "Documentation")]
+        documentation: Option<String>,
+        #[entry(key = "Wants", multiple, subdir = "wants")]
+        wants: Vec<String>,
+    }
+
+    // lets the test change the files behind the cache
+    struct SharedFs(Rc<RefCell<MemoryFs>>);
+
+    impl UnitFs for SharedFs {
+        fn read_to_string(&self, path: &Path) -> io::Result<String> {
+            self.0.borrow().read_to_string(path)
+        }
+
+        fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+            self.0.borrow().read_dir(path)
+        }
+
+        fn metadata(&self, path: &Path) -> io::Result<FileMetadata> {
+            self.0.borrow().metadata(path)
+        }
+
+        fn symlink_metadata(&self, path: &Path) -> io::Result<FileMetadata> {
+            self.0.borrow().symlink_metadata(path)
+        }
+
+        fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+            self.0.borrow().read_link(path)
+        }
+    }
+
+    #[test]
+    fn test_load_cached() {
+        let files = Rc::new(RefCell::new(MemoryFs::new()));
+        files
+            .borrow_mut()
+            .add_file("/etc/foo.service", ".include base.conf\n")
+            .add_file(
+                "/etc/base.conf",
+                "[Unit]\nDescription=foo\nDocumentation=man:foo(1)\n",
+            );
+        let mut cache = UnitCache::with_fs(Rc::new(SharedFs(Rc::clone(&files))));
+        let mut load = || Unit::load_named_cached(vec!["/etc"], "foo", false, &mut cache).unwrap();
+
+        let first = load();
+        assert_eq!(first.unit.description.as_deref(), Some("foo"));
+        assert_eq!(first.unit.documentation.as_deref(), Some("man:foo(1)"));
+        assert_eq!(load(), first);
+
+        files.borrow_mut().add_file(
+            "/etc/foo.service.d/override.conf",
+            "[Unit]\nDescription=dropin\n",
+        );
+        let dropin = load();
+        assert_eq!(dropin.unit.description.as_deref(), Some("dropin"));
+        assert_eq!(load(), dropin);
+
+        files
+            .borrow_mut()
+            .add_symlink("/etc/foo.service.wants/bar.service", "/lib/bar.service");
+        let wants = load();
+        assert_eq!(wants.unit.wants, vec!["bar.service"]);
+        assert_eq!(load(), wants);
+
+        files
+            .borrow_mut()
+            .add_file("/etc/base.conf", "[Unit]\nDocumentation=man:bar(1)\n");
+        let include = load();
+        assert_eq!(include.unit.documentation.as_deref(), Some("man:bar(1)"));
+        assert_eq!(load(), include);
+
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let files = Rc::new(RefCell::new(MemoryFs::new()));
+        let fs = SharedFs(Rc::clone(&files));
+        let paths = vec![PathBuf::from("/etc")];
+        let includes = vec![PathBuf::from("/etc/base.conf")];
+        let current = || {
+            fingerprint(
+                &fs,
+                &paths,
+                "foo.service",
+                "foo.service",
+                "service",
+                &["wants"],
+                &includes,
+            )
+        };
+
+        files.borrow_mut().add_file("/etc/foo.service", "[Unit]\n");
+        let initial = current();
+        assert_eq!(initial, current());
+
+        files
+            .borrow_mut()
+            .add_file("/etc/foo.service", "[Unit]\nDescription=foo\n");
+        let edited = current();
+        assert_ne!(initial, edited);
+
+        files
+            .borrow_mut()
+            .add_file("/etc/foo.service.d/override.conf", "[Unit]\n");
+        let dropin = current();
+        assert_ne!(edited, dropin);
+
+        files.borrow_mut().add_dir("/etc/foo.service.wants");
+        let wants = current();
+        assert_ne!(dropin, wants);
+
+        files.borrow_mut().add_file("/etc/base.conf", "[Service]\n");
+        assert_ne!(wants, current());
+    }
+}
@@ -1,14 +1,18 @@
 use crate::{
+    cache::{load_named_cached, UnitCache},
     error::ReadFileSnafu,
+    fs::{normalize, FileType, StdFs, UnitFs},
     internal::Error,
-    parser::{SectionParser, UnitParser},
+    lexer::{Lexer, Token},
+    parser::{SubdirParser, UnitParser},
     template::{unit_type, UnitType},
 };
 use snafu::ResultExt;
 use std::{
+    borrow::Cow,
+    convert::Infallible,
     ffi::OsString,
-    fs::{read_dir, File},
-    io::Read,
+    fmt,
     net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6},
     num::{
         NonZeroI128, NonZeroI16, NonZeroI32, NonZeroI64, NonZeroI8, NonZeroIsize, NonZeroU128,
@@ -21,100 +25,91 @@ use std::{
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
 
-pub trait UnitConfig: Sized {
-    const SUFFIX: &'static str;
-    fn __parse_unit(__source: UnitParser) -> Result<Self>;
-    fn __patch_unit(__source: UnitParser, __from: &mut Self) -> Result<()>;
+/// The contents of a unit file and its drop-ins, read into memory.
+///
+/// Units whose fields borrow from the file contents (such as `Cow<'a, str>` or `&'a str`) are parsed
+/// from a `UnitFiles` with [`UnitConfig::load_files`], which keeps the buffers alive for as long as the
+/// parsed unit.
+#[derive(Clone)]
+pub struct UnitFiles {
+    fs: Rc<dyn UnitFs>,
+    paths: Rc<Vec<PathBuf>>,
+    filename: String,
+    pub(crate) fragment: (PathBuf, String),
+    pub(crate) dropins: Vec<(PathBuf, String)>,
+    // files read by `inline_includes`, so that caches can tell when they change
+    pub(crate) includes: Vec<PathBuf>,
+}
 
-    fn __load<S: AsRef<Path>>(
-        path: S,
-        paths: Rc<Vec<PathBuf>>,
-        filename: &str,
-        root: bool,
-    ) -> Result<Self> {
-        let path = path.as_ref();
-        let mut file = File::open(path).context(ReadFileSnafu {
-            path: path.to_string_lossy().to_string(),
-        })?;
-        let mut content = String::new();
-        file.read_to_string(&mut content).context(ReadFileSnafu {
-            path: path.to_string_lossy().to_string(),
-        })?;
-        let parser = crate::parser::UnitParser::new(content.as_ref(), paths, root, filename, path)?;
-        Self::__parse_unit(parser)
-    }
-
-    fn __patch<S: AsRef<Path>>(
-        path: S,
-        paths: Rc<Vec<PathBuf>>,
-        filename: &str,
-        from: &mut Self,
-        root: bool,
-    ) -> Result<()> {
-        let path = path.as_ref();
-        let mut file = File::open(path).context(ReadFileSnafu {
-            path: path.to_string_lossy().to_string(),
-        })?;
-        let mut content = String::new();
-        file.read_to_string(&mut content).context(ReadFileSnafu {
-            path: path.to_string_lossy().to_string(),
-        })?;
-        let parser = crate::parser::UnitParser::new(content.as_ref(), paths, root, filename, path)?;
-        Self::__patch_unit(parser, from)
+impl fmt::Debug for UnitFiles {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UnitFiles")
+            .field("paths", &self.paths)
+            .field("filename", &self.filename)
+            .field("fragment", &self.fragment)
+            .field("dropins", &self.dropins)
+            .field("includes", &self.includes)
+            .finish_non_exhaustive()
     }
+}
 
-    fn load<S: AsRef<Path>>(path: S, root: bool) -> Result<Self> {
+impl UnitFiles {
+    /// Reads a single unit file, without looking for drop-ins.
+    pub fn read<S: AsRef<Path>>(path: S) -> Result<Self> {
+        Self::read_from(Rc::new(StdFs), path)
+    }
+
+    /// Same as [`UnitFiles::read`], but reads from `fs` instead of the disk.
+    pub fn read_from<S: AsRef<Path>>(fs: Rc<dyn UnitFs>, path: S) -> Result<Self> {
         let path = path.as_ref();
-        let empty_vec: Vec<PathBuf> = Vec::new();
-        let paths = Rc::new(empty_vec);
-        Self::__load(
-            path,
-            paths,
-            path.file_name()
-                .map_or("".to_string(), |x| x.to_string_lossy().to_string())
-                .as_str(),
-            root,
-        )
+        let content = read_file(fs.as_ref(), path)?;
+        Ok(Self {
+            fs,
+            paths: Rc::new(Vec::new()),
+            filename: path
+                .file_name()
+                .map_or("".to_string(), |x| x.to_string_lossy().to_string()),
+            fragment: (path.to_owned(), content),
+            dropins: Vec::new(),
+            includes: Vec::new(),
+        })
     }
 
-    fn load_named<S: AsRef<str>, P: AsRef<Path>>(
+    /// Looks up the unit named `name` with the given suffix in `paths`, then reads it along with all of
+    /// its drop-ins.
+    pub fn read_named<S: AsRef<str>, P: AsRef<Path>>(
         paths: Vec<P>,
         name: S,
-        root: bool,
+        suffix: &str,
+    ) -> Result<Self> {
+        Self::read_named_from(Rc::new(StdFs), paths, name, suffix)
+    }
+
+    /// Same as [`UnitFiles::read_named`], but reads from `fs` instead of the disk.
+    pub fn read_named_from<S: AsRef<str>, P: AsRef<Path>>(
+        fs: Rc<dyn UnitFs>,
+        paths: Vec<P>,
+        name: S,
+        suffix: &str,
     ) -> Result<Self> {
         // return when first one is found?
         let paths: Vec<PathBuf> = paths.iter().map(|x| x.as_ref().to_path_buf()).collect();
-        let paths_rc = Rc::new(paths);
         let name = name.as_ref();
-        let fullname = if name.ends_with(Self::SUFFIX) {
-            name.to_string()
-        } else {
-            format!("{}.{}", name, Self::SUFFIX)
-        };
-        let actual_file_name = match unit_type(fullname.as_str())? {
-            UnitType::Template(_) => {
-                return Err(Error::LoadTemplateError {
-                    name: fullname.to_owned(),
-                });
-            }
-            UnitType::Instance(_, template_filename) => template_filename,
-            UnitType::Regular(_) => fullname.to_owned(),
-        };
-        let mut result = None;
+        let (fullname, actual_file_name) = unit_file_names(name, suffix)?;
 
         // load itself
-        let paths = Rc::clone(&paths_rc);
-        for dir in (*paths).iter() {
+        let mut fragment = None;
+        for dir in paths.iter() {
             let mut path = dir.to_owned();
             path.push(actual_file_name.as_str());
-            if let Ok(res) = Self::__load(path, Rc::clone(&paths_rc), fullname.as_str(), root) {
-                result = Some(res);
+            if let Ok(content) = read_file(fs.as_ref(), &path) {
+                fragment = Some((path, content));
                 break;
             }
         }
 
-        let mut result = if let Some(result) = result {
-            result
+        let fragment = if let Some(fragment) = fragment {
+            fragment
         } else {
             return Err(Error::NoUnitFoundError {
                 name: name.to_string(),
@@ -122,38 +117,24 @@ pub trait UnitConfig: Sized {
         };
 
         // load drop-ins
-        let mut dropin_dir_names = vec![
-            format!("{}.d", Self::SUFFIX),
-            format!("{}.d", fullname.as_str()),
-        ];
-        let segments: Vec<&str> = fullname.split('-').collect();
-        for i in (1..segments.len()).rev() {
-            let segmented = segments[0..i].join("-");
-            let dir_name = format!("{}-.{}.d", segmented, Self::SUFFIX);
-            dropin_dir_names.push(dir_name);
-        }
+        let dropin_dir_names = dropin_dir_names(&fullname, suffix);
 
+        let mut dropins = Vec::new();
         for dir_name in dropin_dir_names.iter() {
-            for dir in (*paths).iter() {
+            for dir in paths.iter() {
                 let mut path = dir.to_owned();
                 path.push(dir_name.as_str());
-                if path.is_dir() {
-                    if let Ok(dir_entries) = read_dir(&path) {
-                        for item in dir_entries {
-                            if let Ok(entry) = item {
-                                if let Ok(meta) = entry.metadata() {
-                                    if meta.is_file()
-                                        && entry.path().extension().is_some_and(|x| x == "conf")
-                                    {
-                                        let paths = Rc::clone(&paths_rc);
-                                        if let Err(err) = Self::__patch(
-                                            entry.path(),
-                                            paths,
-                                            fullname.as_str(),
-                                            &mut result,
-                                            root,
-                                        ) {
-                                            log::warn!("Failed to patch unit {}: {})", name, err);
+                if fs.is_dir(&path) {
+                    if let Ok(dir_entries) = fs.read_dir(&path) {
+                        for entry in dir_entries {
+                            if let Ok(meta) = fs.metadata(&entry) {
+                                if meta.file_type == FileType::File
+                                    && entry.extension().is_some_and(|x| x == "conf")
+                                {
+                                    match read_file(fs.as_ref(), &entry) {
+                                        Ok(content) => dropins.push((entry, content)),
+                                        Err(err) => {
+                                            log::warn!("Failed to patch unit {}: {})", name, err)
                                         }
                                     }
                                 }
@@ -164,20 +145,392 @@ pub trait UnitConfig: Sized {
             }
         }
 
+        Ok(Self {
+            fs,
+            paths: Rc::new(paths),
+            filename: fullname,
+            fragment,
+            dropins,
+            includes: Vec::new(),
+        })
+    }
+
+    /// Replaces legacy `.include` directives in the unit file and its drop-ins with the contents of
+    /// the files they reference.
+    ///
+    /// Relative paths are resolved against the directory of the including file, and entries following
+    /// a directive still belong to the section it appeared in.
+    pub fn inline_includes(mut self) -> Result<Self> {
+        let fs = self.fs.as_ref();
+        let includes = &mut self.includes;
+        let (path, content) = &mut self.fragment;
+        *content = inline_includes(fs, path, content, &mut Vec::new(), includes)?;
+        for (path, content) in self.dropins.iter_mut() {
+            *content = inline_includes(fs, path, content, &mut Vec::new(), includes)?;
+        }
+        Ok(self)
+    }
+}
+
+// deeper nesting is taken for a cycle that lexical normalization cannot see, such as a symlink loop
+const MAX_INCLUDE_DEPTH: usize = 32;
+
+// `stack` holds the files currently being included, to detect cycles, and `includes` collects every
+// file that was read
+fn inline_includes(
+    fs: &dyn UnitFs,
+    path: &Path,
+    content: &str,
+    stack: &mut Vec<PathBuf>,
+    includes: &mut Vec<PathBuf>,
+) -> Result<String> {
+    let mut lexer = Lexer::new(content);
+    let mut result = String::new();
+    let mut copied = 0;
+    let mut section = None;
+    stack.push(normalize(path));
+    // malformed lines are left for the parser to report
+    while let Some(token) = lexer.next() {
+        match token {
+            Ok(Token::Section(name)) => section = Some(name),
+            Ok(Token::Include { path: target, line }) => {
+                log::warn!(
+                    "{}:{}: .include directives are deprecated, and support for them will be removed in a future version of systemd. Please use drop-in files instead.",
+                    path.to_string_lossy(),
+                    lexer.line()
+                );
+                let target = normalize(&path.parent().unwrap_or(Path::new("/")).join(target));
+                if stack.contains(&target) || stack.len() >= MAX_INCLUDE_DEPTH {
+                    return Err(Error::IncludeCycleError {
+                        path: target.to_string_lossy().to_string(),
+                    });
+                }
+                let included = read_file(fs, &target)?;
+                includes.push(target.clone());
+
+                let start = lexer.offset(line);
+                result.push_str(&content[copied..start]);
+                result.push_str(&inline_includes(fs, &target, &included, stack, includes)?);
+                if !result.ends_with('\n') {
+                    result.push('\n');
+                }
+                // the included file may end in another section
+                if let Some(section) = section {
+                    result.push_str(&format!("[{}]\n", section));
+                }
+                copied = start + line.len();
+            }
+            _ => {}
+        }
+    }
+    stack.pop();
+    if copied == 0 {
+        return Ok(content.to_string());
+    }
+    result.push_str(&content[copied..]);
+    Ok(result)
+}
+
+// returns the full name of the unit, along with the name of the file to read it from, which differs for
+// instances of templates
+pub(crate) fn unit_file_names(name: &str, suffix: &str) -> Result<(String, String)> {
+    let fullname = if name.ends_with(&format!(".{}", suffix)) {
+        name.to_string()
+    } else {
+        format!("{}.{}", name, suffix)
+    };
+    let actual_file_name = match unit_type(fullname.as_str())? {
+        UnitType::Template(_) => {
+            return Err(Error::LoadTemplateError {
+                name: fullname.to_owned(),
+            });
+        }
+        UnitType::Instance(_, _, template_filename) => template_filename,
+        UnitType::Regular(_) => fullname.to_owned(),
+    };
+    Ok((fullname, actual_file_name))
+}
+
+pub(crate) fn dropin_dir_names(fullname: &str, suffix: &str) -> Vec<String> {
+    let mut result = vec![format!("{}.d", suffix), format!("{}.d", fullname)];
+    let segments: Vec<&str> = fullname.split('-').collect();
+    for i in (1..segments.len()).rev() {
+        let segmented = segments[0..i].join("-");
+        result.push(format!("{}-.{}.d", segmented, suffix));
+    }
+    result
+}
+
+fn read_file(fs: &dyn UnitFs, path: &Path) -> Result<String> {
+    fs.read_to_string(path).context(ReadFileSnafu {
+        path: path.to_string_lossy().to_string(),
+    })
+}
+
+pub trait UnitConfig<'a>: Sized {
+    const SUFFIX: &'static str;
+    /// Whether legacy `.include` directives are inlined when loading the unit.
+    const INCLUDE: bool = false;
+    fn __parse_unit(__source: UnitParser<'a>) -> Result<Self>;
+    fn __patch_unit(__source: UnitParser<'a>, __from: &mut Self) -> Result<()>;
+
+    /// The `subdir`s (such as `wants`) that entries of this unit are collected from.
+    fn __subdirs() -> Vec<&'static str> {
+        Vec::new()
+    }
+
+    /// Adds the units found in the `subdir`s of the unit to the corresponding entries.
+    fn __collect_subdirs(&mut self, __parser: &SubdirParser) {}
+
+    /// Parses a unit from files already read into memory, borrowing from them where possible.
+    fn load_files(files: &'a UnitFiles, root: bool) -> Result<Self> {
+        let (path, content) = &files.fragment;
+        let parser = UnitParser::new(content, root, &files.filename, path)?;
+        let mut result = Self::__parse_unit(parser)?;
+
+        for (path, content) in files.dropins.iter() {
+            if let Err(err) = UnitParser::new(content, root, &files.filename, path)
+                .and_then(|parser| Self::__patch_unit(parser, &mut result))
+            {
+                log::warn!("Failed to patch unit {}: {})", files.filename, err);
+            }
+        }
+
+        // subdirs are collected no matter whether the unit file mentions the entries
+        result.__collect_subdirs(&SubdirParser::new(
+            Rc::clone(&files.fs),
+            Rc::clone(&files.paths),
+            &files.filename,
+        ));
+
         Ok(result)
     }
 }
 
-pub trait UnitSection: Sized {
-    fn __parse_section(__source: SectionParser) -> Result<Option<Self>>;
-    fn __patch_section(__source: SectionParser, __from: &mut Self) -> Result<()>;
+/// Loads units that own all of their values, reading the files on the fly.
+///
+/// This is implemented for every unit that does not borrow from the unit files; those that do should
+/// be loaded from a [`UnitFiles`] with [`UnitConfig::load_files`] instead.
+pub trait UnitLoad: Sized {
+    fn load<S: AsRef<Path>>(path: S, root: bool) -> Result<Self>;
+    fn load_named<S: AsRef<str>, P: AsRef<Path>>(
+        paths: Vec<P>,
+        name: S,
+        root: bool,
+    ) -> Result<Self>;
+
+    /// Same as [`UnitLoad::load`], but reads from `fs` instead of the disk.
+    fn load_from<S: AsRef<Path>>(fs: Rc<dyn UnitFs>, path: S, root: bool) -> Result<Self>;
+
+    /// Same as [`UnitLoad::load_named`], but reads from `fs` instead of the disk.
+    fn load_named_from<S: AsRef<str>, P: AsRef<Path>>(
+        fs: Rc<dyn UnitFs>,
+        paths: Vec<P>,
+        name: S,
+        root: bool,
+    ) -> Result<Self>;
+
+    /// Same as [`UnitLoad::load_named`], but returns a clone of the unit in `cache` if none of the
+    /// files it was loaded from changed since. Units are read from the filesystem of `cache`.
+    fn load_named_cached<S: AsRef<str>, P: AsRef<Path>>(
+        paths: Vec<P>,
+        name: S,
+        root: bool,
+        cache: &mut UnitCache<Self>,
+    ) -> Result<Self>
+    where
+        Self: Clone;
+}
+
+impl<T: for<'a> UnitConfig<'a>> UnitLoad for T {
+    fn load<S: AsRef<Path>>(path: S, root: bool) -> Result<Self> {
+        Self::load_from(Rc::new(StdFs), path, root)
+    }
+
+    fn load_named<S: AsRef<str>, P: AsRef<Path>>(
+        paths: Vec<P>,
+        name: S,
+        root: bool,
+    ) -> Result<Self> {
+        Self::load_named_from(Rc::new(StdFs), paths, name, root)
+    }
+
+    fn load_from<S: AsRef<Path>>(fs: Rc<dyn UnitFs>, path: S, root: bool) -> Result<Self> {
+        let mut files = UnitFiles::read_from(fs, path)?;
+        if Self::INCLUDE {
+            files = files.inline_includes()?;
+        }
+        Self::load_files(&files, root)
+    }
+
+    fn load_named_from<S: AsRef<str>, P: AsRef<Path>>(
+        fs: Rc<dyn UnitFs>,
+        paths: Vec<P>,
+        name: S,
+        root: bool,
+    ) -> Result<Self> {
+        let mut files = UnitFiles::read_named_from(fs, paths, name, <Self as UnitConfig>::SUFFIX)?;
+        if <Self as UnitConfig>::INCLUDE {
+            files = files.inline_includes()?;
+        }
+        Self::load_files(&files, root)
+    }
+
+    fn load_named_cached<S: AsRef<str>, P: AsRef<Path>>(
+        paths: Vec<P>,
+        name: S,
+        root: bool,
+        cache: &mut UnitCache<Self>,
+    ) -> Result<Self>
+    where
+        Self: Clone,
+    {
+        let paths = paths.iter().map(|x| x.as_ref().to_path_buf()).collect();
+        load_named_cached(paths, name.as_ref(), root, cache)
+    }
+}
+
+/// A section of a unit, parsed from its entries as produced by a [`SectionParser`](crate::internal::SectionParser).
+///
+/// Sections flattened into another one are parsed from the entries collected by the outer section.
+pub trait UnitSection<'a>: Sized {
+    /// The keys of the section and of its flattened sections, checked for collisions at compile time.
+    const __KEYS: SectionKeys = SectionKeys {
+        keys: &[],
+        flattened: &[],
+    };
+
+    fn __parse_section<I: Iterator<Item = Result<(&'a str, Cow<'a, str>)>>>(
+        __source: I,
+    ) -> Result<Option<Self>>;
+    fn __patch_section<I: Iterator<Item = Result<(&'a str, Cow<'a, str>)>>>(
+        __source: I,
+        __from: &mut Self,
+    ) -> Result<()>;
+
+    /// Whether an entry with this key belongs to the section, including its flattened sections.
+    fn __has_key(__key: &str) -> bool {
+        false
+    }
+
+    fn __subdirs() -> Vec<&'static str> {
+        Vec::new()
+    }
+
+    fn __collect_subdirs(&mut self, __parser: &SubdirParser) {}
+}
+
+/// The keys (including aliases and deprecated names) accepted by a [`UnitSection`], as a tree of its
+/// flattened sections.
+pub struct SectionKeys {
+    pub keys: &'static [&'static str],
+    pub flattened: &'static [&'static SectionKeys],
+}
+
+impl SectionKeys {
+    /// Returns a key accepted more than once, by the section itself and one of its flattened sections or
+    /// by two flattened sections, in which case the order of the fields would decide where it goes.
+    pub const fn duplicate(&self) -> Option<&'static str> {
+        self.duplicate_of(self)
+    }
+
+    const fn duplicate_of(&self, root: &SectionKeys) -> Option<&'static str> {
+        let mut i = 0;
+        while i < self.keys.len() {
+            if root.count(self.keys[i]) > 1 {
+                return Some(self.keys[i]);
+            }
+            i += 1;
+        }
+        let mut i = 0;
+        while i < self.flattened.len() {
+            if let Some(key) = self.flattened[i].duplicate_of(root) {
+                return Some(key);
+            }
+            i += 1;
+        }
+        None
+    }
+
+    const fn count(&self, key: &str) -> usize {
+        let mut result = 0;
+        let mut i = 0;
+        while i < self.keys.len() {
+            if str_eq(self.keys[i], key) {
+                result += 1;
+            }
+            i += 1;
+        }
+        let mut i = 0;
+        while i < self.flattened.len() {
+            result += self.flattened[i].count(key);
+            i += 1;
+        }
+        result
+    }
+}
+
+// `==` on `str` is not `const`
+const fn str_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
 }
 
 pub trait UnitEntry: Sized {
-    type Error;
+    /// Reported as the source of [`Error::ValueParsingError`] when a required entry fails to parse, or
+    /// logged otherwise.
+    type Error: std::error::Error + Send + Sync + 'static;
+    /// Whether `multiple` entries split assignments into words and parse each of them. Types parsed from
+    /// a whole assignment, such as [`ExecCommand`](crate::prelude::ExecCommand), set this to `false`.
+    const SPLIT: bool = true;
     fn parse_from_str<S: AsRef<str>>(input: S) -> std::result::Result<Self, Self::Error>;
 }
 
+/// Parses an entry value that may borrow from the unit file.
+///
+/// This is implemented for every [`UnitEntry`], as well as for `Cow<'a, str>` and `&'a str`. The latter
+/// fails when the value cannot be borrowed, i.e. when it was rewritten while parsing.
+pub trait UnitEntryRef<'a>: Sized {
+    type Error: std::error::Error + Send + Sync + 'static;
+    const SPLIT: bool = true;
+    fn parse_from_cow(input: Cow<'a, str>) -> std::result::Result<Self, Self::Error>;
+}
+
+impl<'a, T: UnitEntry> UnitEntryRef<'a> for T {
+    type Error = <T as UnitEntry>::Error;
+    const SPLIT: bool = <T as UnitEntry>::SPLIT;
+    fn parse_from_cow(input: Cow<'a, str>) -> std::result::Result<Self, Self::Error> {
+        Self::parse_from_str(input)
+    }
+}
+
+impl<'a> UnitEntryRef<'a> for Cow<'a, str> {
+    type Error = Infallible;
+    fn parse_from_cow(input: Cow<'a, str>) -> std::result::Result<Self, Self::Error> {
+        Ok(input)
+    }
+}
+
+impl<'a> UnitEntryRef<'a> for &'a str {
+    type Error = Error;
+    fn parse_from_cow(input: Cow<'a, str>) -> std::result::Result<Self, Self::Error> {
+        match input {
+            Cow::Borrowed(inner) => Ok(inner),
+            Cow::Owned(value) => Err(Error::BorrowValueError { value }),
+        }
+    }
+}
+
 macro_rules! impl_for_types {
     ($typ:ty) => {
         impl UnitEntry for $typ {
@@ -235,12 +588,131 @@ impl_for_types!(
 );
 
 impl UnitEntry for bool {
-    type Error = ();
+    type Error = Error;
     fn parse_from_str<S: AsRef<str>>(input: S) -> std::result::Result<Self, Self::Error> {
         match input.as_ref() {
             "1" | "yes" | "true" | "on" => Ok(true),
             "0" | "no" | "false" | "off" => Ok(false),
-            _ => Err(()),
+            value => Err(Error::UnknownValueError {
+                value: value.to_string(),
+                expected: "1, yes, true, on, 0, no, false, off".to_string(),
+            }),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        config::{SectionKeys, UnitEntry, UnitEntryRef, UnitFiles},
+        error::Error,
+        fs::MemoryFs,
+    };
+    use std::{borrow::Cow, path::PathBuf, rc::Rc};
+
+    #[test]
+    fn test_inline_includes() {
+        let mut fs = MemoryFs::new();
+        fs.add_file(
+            "/etc/foo.service",
+            "[Unit]\n.include base.conf\nDescription=foo\n",
+        )
+        .add_file("/etc/base.conf", "[Service]\n.include /lib/exec.conf\n")
+        .add_file("/lib/exec.conf", "ExecStart=/bin/true\n")
+        .add_file("/etc/loop.service", "[Unit]\n.include loop.service\n")
+        .add_file(
+            "/etc/dotdot.service",
+            "[Unit]\n.include ../etc/dotdot.service\n",
+        )
+        .add_file("/etc/ping.service", "[Unit]\n.include pong.conf\n")
+        .add_file("/etc/pong.conf", "[Unit]\n.include ./ping.service\n");
+        let fs = Rc::new(fs);
+
+        let files = UnitFiles::read_from(fs.clone(), "/etc/foo.service")
+            .unwrap()
+            .inline_includes()
+            .unwrap();
+        assert_eq!(
+            files.fragment.1,
+            "[Unit]\n[Service]\nExecStart=/bin/true\n[Service]\n\n[Unit]\n\nDescription=foo\n"
+        );
+        assert_eq!(
+            files.includes,
+            vec![
+                PathBuf::from("/etc/base.conf"),
+                PathBuf::from("/lib/exec.conf")
+            ]
+        );
+
+        for name in ["loop", "dotdot", "ping"] {
+            let files = UnitFiles::read_from(fs.clone(), format!("/etc/{}.service", name)).unwrap();
+            assert!(matches!(
+                files.inline_includes(),
+                Err(Error::IncludeCycleError { .. })
+            ));
+        }
+
+        // too deep to be anything but a cycle
+        let mut fs = MemoryFs::new();
+        for i in 0..40 {
+            fs.add_file(
+                format!("/etc/{}.conf", i),
+                format!("[Unit]\n.include {}.conf\n", i + 1),
+            );
+        }
+        let files = UnitFiles::read_from(Rc::new(fs), "/etc/0.conf").unwrap();
+        assert!(matches!(
+            files.inline_includes(),
+            Err(Error::IncludeCycleError { .. })
+        ));
+    }
+
+    #[test]
+    fn test_entry_errors() {
+        assert!(bool::parse_from_str("yes").unwrap());
+        assert_eq!(
+            bool::parse_from_str("maybe").unwrap_err().to_string(),
+            "Unknown value maybe, expected one of 1, yes, true, on, 0, no, false, off."
+        );
+        assert!(matches!(
+            <&str>::parse_from_cow(Cow::Owned("foo".to_string())),
+            Err(Error::BorrowValueError { .. })
+        ));
+
+        let error = Error::ValueParsingError {
+            key: "Nice".to_string(),
+            value: "x".to_string(),
+            source: Box::new(i8::parse_from_str("x").unwrap_err()),
+        };
+        assert_eq!(
+            error.to_string(),
+            "Failed to parse x as the value of entry with key Nice: invalid digit found in string"
+        );
+        assert!(std::error::Error::source(&error).is_some());
+    }
+
+    #[test]
+    fn test_duplicate_keys() {
+        const SERVICE: SectionKeys = SectionKeys {
+            keys: &["User", "Group"],
+            flattened: &[],
+        };
+        const EXEC: SectionKeys = SectionKeys {
+            keys: &["ExecStart", "WorkingDirectory"],
+            flattened: &[&SERVICE],
+        };
+        assert_eq!(EXEC.duplicate(), None);
+
+        const OUTER: SectionKeys = SectionKeys {
+            keys: &["Type"],
+            flattened: &[&EXEC, &SERVICE],
+        };
+        assert_eq!(OUTER.duplicate(), Some("User"));
+
+        const SHADOWED: SectionKeys = SectionKeys {
+            keys: &["Group"],
+            flattened: &[&EXEC],
+        };
+        assert_eq!(SHADOWED.duplicate(), Some("Group"));
+    }
+}
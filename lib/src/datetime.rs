@@ -1,9 +1,9 @@
-use crate::{config::UnitEntry, duration::duration_from_parser};
+use crate::{config::UnitEntry, duration::TimeSpan};
 use chrono::{prelude::*, Duration};
-use chrono_tz::{Tz, UTC};
+use chrono_tz::Tz;
 use pest::{
     error::{Error, ErrorVariant},
-    Parser,
+    Parser, Span,
 };
 use pest_derive::Parser;
 
@@ -25,230 +25,300 @@ fn map_weekday(rule: Rule) -> Weekday {
 }
 
 impl UnitEntry for chrono::DateTime<Utc> {
-    type Error = Error<Rule>;
+    type Error = Box<Error<Rule>>;
     fn parse_from_str<S: AsRef<str>>(input: S) -> std::result::Result<Self, Self::Error> {
-        let now = Utc::now();
-        let now_date = now.date_naive();
-
-        let mut parser = DatetimeParser::parse(Rule::timestamp, input.as_ref())?;
-        let timestamp = parser.next().unwrap().into_inner().next().unwrap();
-        let span = timestamp.as_span();
-        match timestamp.as_rule() {
-            Rule::full_len => {
-                let mut full_len = timestamp.into_inner();
-                let mut next = full_len.next();
-                let weekday = if next.as_ref().unwrap().as_rule() == Rule::weekday {
-                    let weekday_rule = next.unwrap().into_inner().next().unwrap().as_rule();
-                    next = full_len.next();
-                    Some(map_weekday(weekday_rule))
-                } else {
-                    None
-                };
-                // this one is either date or time
-                let (year, month, day) = if next.as_ref().unwrap().as_rule() == Rule::date {
-                    let mut date = next.unwrap().into_inner();
-                    let year = date.next().unwrap();
-                    let year: i32 = match year.as_rule() {
-                        Rule::number_4b => year.as_str().parse().unwrap(),
-                        Rule::number_2b => year.as_str().parse::<i32>().unwrap() + 2000,
-                        _ => unreachable!(),
-                    };
-                    let month: u32 = date.next().unwrap().as_str().parse().unwrap();
-                    let day: u32 = date.next().unwrap().as_str().parse().unwrap();
+        parse_timestamp_at(input.as_ref(), Utc::now(), &Local)
+    }
+}
 
-                    next = full_len.next();
-                    (year, month, day)
-                } else {
-                    (now_date.year(), now_date.month(), now_date.day())
-                };
-                // this one could be time, timezone or None
-                let (hour, minute, second, microsecond) =
-                    if next.as_ref().is_some_and(|x| x.as_rule() == Rule::time) {
-                        let mut time = next.unwrap().into_inner();
-                        let hour: u32 = time.next().unwrap().as_str().parse().unwrap();
-                        let minute: u32 = time.next().unwrap().as_str().parse().unwrap();
-                        let second: u32 = time.next().map_or(0, |x| x.as_str().parse().unwrap());
-                        let microsecond: i64 =
-                            time.next().map_or(0, |x| x.as_str().parse().unwrap());
+fn custom_error(message: &str, span: Span<'_>) -> Box<Error<Rule>> {
+    Box::new(Error::new_from_span(
+        ErrorVariant::CustomError {
+            message: message.to_string(),
+        },
+        span,
+    ))
+}
 
-                        next = full_len.next();
-                        (hour, minute, second, microsecond)
-                    } else {
-                        (0, 0, 0, 0)
-                    };
+// the instant of `date` and `time` in `tz`, preferring the earlier one if ambiguous because of DST
+fn local_datetime<T: TimeZone>(tz: &T, date: NaiveDate, time: NaiveTime) -> Option<DateTime<Utc>> {
+    tz.from_local_datetime(&date.and_time(time))
+        .earliest()
+        .map(|x| x.with_timezone(&Utc))
+}
 
-                // this one could be timezone or None
-                let timezone = if next.as_ref().is_some_and(|x| x.as_rule() == Rule::timezone) {
-                    let span = next.as_ref().unwrap().as_span();
-                    if let Ok(tz) = next.unwrap().as_str().parse::<Tz>() {
-                        Some(tz)
-                    } else {
-                        return Err(Error::new_from_span(
-                            ErrorVariant::CustomError {
-                                message: "Failed to parse timezone.".to_string(),
-                            },
-                            span,
-                        ));
-                    }
-                } else {
-                    None
-                };
+/// Parses a timestamp as described in systemd.time, relative to `now`.
+///
+/// Timestamps without a timezone, as well as `today`, `yesterday`, `tomorrow` and times without a date,
+/// are interpreted in `local_tz`, which is the system's local timezone ([`chrono::Local`]) when parsing
+/// through [`UnitEntry`]. Relative timestamps (`+3h`, `-5s`, `3h left`, `11min ago`) accept any time span
+/// that [`TimeSpan`] does, as does `@` followed by the seconds since `epoch`.
+pub fn parse_timestamp_at<T: TimeZone>(
+    input: &str,
+    now: DateTime<Utc>,
+    local_tz: &T,
+) -> std::result::Result<DateTime<Utc>, Box<Error<Rule>>> {
+    let whole = Span::new(input, 0, input.len()).unwrap();
+    let trimmed = input.trim_matches(' ');
+    let span = |input: &str| match TimeSpan::parse_from_str(input) {
+        Ok(TimeSpan::Finite(duration)) => Ok(duration),
+        _ => Err(custom_error("Invalid time span.", whole)),
+    };
+    if let Some(relative) = trimmed.strip_prefix('+') {
+        return Ok(now + span(relative)?);
+    }
+    if let Some(relative) = trimmed.strip_prefix('-') {
+        return Ok(now - span(relative)?);
+    }
+    if let Some(relative) = trimmed.strip_suffix(" left") {
+        return Ok(now + span(relative)?);
+    }
+    if let Some(relative) = trimmed.strip_suffix(" ago") {
+        return Ok(now - span(relative)?);
+    }
+    if let Some(absolute) = trimmed.strip_prefix('@') {
+        return Ok(Utc.timestamp_opt(0, 0).unwrap() + span(absolute)?);
+    }
 
-                let res = match timezone {
-                    Some(tz) => tz
-                        .with_ymd_and_hms(year, month, day, hour, minute, second)
-                        .single()
-                        .ok_or(Error::new_from_span(
-                            ErrorVariant::CustomError {
-                                message: "Failed to create timestamp.".to_string(),
-                            },
-                            span,
-                        ))?
-                        .with_timezone(&Utc),
-                    None => Utc
-                        .with_ymd_and_hms(year, month, day, hour, minute, second)
-                        .single()
-                        .ok_or(Error::new_from_span(
-                            ErrorVariant::CustomError {
-                                message: "Failed to create timestamp.".to_string(),
-                            },
-                            span,
-                        ))?,
+    let mut parser = DatetimeParser::parse(Rule::timestamp, input)?;
+    let timestamp = parser.next().unwrap().into_inner().next().unwrap();
+    let span = timestamp.as_span();
+    match timestamp.as_rule() {
+        Rule::full_len => {
+            let mut full_len = timestamp.into_inner();
+            let mut next = full_len.next();
+            let weekday = if next.as_ref().unwrap().as_rule() == Rule::weekday {
+                let weekday_rule = next.unwrap().into_inner().next().unwrap().as_rule();
+                next = full_len.next();
+                Some(map_weekday(weekday_rule))
+            } else {
+                None
+            };
+            // this one is either date or time
+            let date = if next.as_ref().unwrap().as_rule() == Rule::date {
+                let mut date = next.unwrap().into_inner();
+                let year = date.next().unwrap();
+                let year: i32 = match year.as_rule() {
+                    Rule::number_4b => year.as_str().parse().unwrap(),
+                    Rule::number_2b => year.as_str().parse::<i32>().unwrap() + 2000,
+                    _ => unreachable!(),
                 };
-                let res = res + Duration::microseconds(microsecond);
+                let month: u32 = date.next().unwrap().as_str().parse().unwrap();
+                let day: u32 = date.next().unwrap().as_str().parse().unwrap();
 
-                // weekday validation
-                if let Some(weekday) = weekday {
-                    if weekday != res.weekday() {
-                        return Err(Error::new_from_span(
-                            ErrorVariant::CustomError {
-                                message: "The specified weekday does not match the specified date."
-                                    .to_string(),
-                            },
-                            span,
-                        ));
-                    }
-                }
-                Ok(res)
-            }
-            Rule::relative => {
-                let now = Utc::now();
+                next = full_len.next();
+                Some(
+                    NaiveDate::from_ymd_opt(year, month, day)
+                        .ok_or(custom_error("Invalid date.", span))?,
+                )
+            } else {
+                None
+            };
+            // this one could be time, timezone or None
+            let time = if next.as_ref().is_some_and(|x| x.as_rule() == Rule::time) {
+                let mut time = next.unwrap().into_inner();
+                let hour: u32 = time.next().unwrap().as_str().parse().unwrap();
+                let minute: u32 = time.next().unwrap().as_str().parse().unwrap();
+                let second: u32 = time.next().map_or(0, |x| x.as_str().parse().unwrap());
+                // fractions of a second, with microsecond precision
+                let microsecond: u32 = time
+                    .next()
+                    .map_or(0, |x| format!("{:0<6.6}", x.as_str()).parse().unwrap());
 
-                let mut relative = timestamp.into_inner();
+                next = full_len.next();
+                NaiveTime::from_hms_micro_opt(hour, minute, second, microsecond)
+                    .ok_or(custom_error("Invalid time.", span))?
+            } else {
+                NaiveTime::MIN
+            };
 
-                let variant = relative.next().unwrap();
-                let rule = variant.as_rule();
+            // this one could be timezone or None
+            let timezone =
+                match next {
+                    Some(timezone) => Some(timezone.as_str().parse::<Tz>().map_err(|_| {
+                        custom_error("Failed to parse timezone.", timezone.as_span())
+                    })?),
+                    None => None,
+                };
 
-                let timespan = variant.into_inner();
-                let duration = duration_from_parser(timespan)?;
-                match rule {
-                    Rule::relative_forward => Ok(now + duration),
-                    Rule::relative_backward => Ok(now - duration),
-                    _ => unreachable!(),
-                }
+            // dates default to today in the timezone of the timestamp
+            let date = match (date, timezone) {
+                (Some(date), _) => date,
+                (None, Some(tz)) => now.with_timezone(&tz).date_naive(),
+                (None, None) => now.with_timezone(local_tz).date_naive(),
+            };
+            if weekday.is_some_and(|x| x != date.weekday()) {
+                return Err(custom_error(
+                    "The specified weekday does not match the specified date.",
+                    span,
+                ));
             }
-            Rule::absolute => {
-                let offset = input.as_ref()[1..].parse().unwrap();
-                let naive =
-                    NaiveDateTime::from_timestamp_opt(offset, 0).ok_or(Error::new_from_span(
-                        ErrorVariant::CustomError {
-                            message: "Invalid absolute timestamp.".to_string(),
-                        },
-                        span,
-                    ))?;
-                Ok(Utc.from_utc_datetime(&naive))
+            match timezone {
+                Some(tz) => local_datetime(&tz, date, time),
+                None => local_datetime(local_tz, date, time),
             }
-            Rule::special => {
-                let mut special = timestamp.into_inner();
-                let keyword = special.next().unwrap();
-                let timezone: Tz = if let Some(timezone) = special.next() {
-                    if let Ok(tz) = timezone.as_str().parse() {
-                        tz
-                    } else {
-                        UTC
-                    }
-                } else {
-                    UTC
+            .ok_or(custom_error("Failed to create timestamp.", span))
+        }
+        Rule::special => {
+            let mut special = timestamp.into_inner();
+            let keyword = special.next().unwrap().as_rule();
+            let timezone =
+                match special.next() {
+                    Some(timezone) => Some(timezone.as_str().parse::<Tz>().map_err(|_| {
+                        custom_error("Failed to parse timezone.", timezone.as_span())
+                    })?),
+                    None => None,
                 };
 
-                match keyword.as_rule() {
-                    Rule::today => Ok(timezone
-                        .with_ymd_and_hms(
-                            now_date.year(),
-                            now_date.month(),
-                            now_date.day(),
-                            0,
-                            0,
-                            0,
-                        )
-                        .single()
-                        .ok_or(Error::new_from_span(
-                            ErrorVariant::CustomError {
-                                message: "Invalid timestamp.".to_string(),
-                            },
-                            span,
-                        ))?
-                        .with_timezone(&Utc)),
-                    Rule::tomorrow => Ok(timezone
-                        .with_ymd_and_hms(
-                            now_date.year(),
-                            now_date.month(),
-                            now_date.day(),
-                            0,
-                            0,
-                            0,
-                        )
-                        .single()
-                        .ok_or(Error::new_from_span(
-                            ErrorVariant::CustomError {
-                                message: "Invalid timestamp.".to_string(),
-                            },
-                            span,
-                        ))?
-                        .with_timezone(&Utc)
-                        + Duration::days(1)),
-                    Rule::yesturday => Ok(timezone
-                        .with_ymd_and_hms(
-                            now_date.year(),
-                            now_date.month(),
-                            now_date.day(),
-                            0,
-                            0,
-                            0,
-                        )
-                        .single()
-                        .ok_or(Error::new_from_span(
-                            ErrorVariant::CustomError {
-                                message: "Invalid timestamp.".to_string(),
-                            },
-                            span,
-                        ))?
-                        .with_timezone(&Utc)
-                        - Duration::days(1)),
-                    Rule::now => Ok(now),
-                    _ => unreachable!(),
+            let days = match keyword {
+                Rule::now => return Ok(now),
+                Rule::epoch => return Ok(Utc.timestamp_opt(0, 0).unwrap()),
+                Rule::yesterday => -1,
+                Rule::today => 0,
+                Rule::tomorrow => 1,
+                _ => unreachable!(),
+            };
+            // midnight of the day in the given timezone, which need not be 24 hours after the previous one
+            match timezone {
+                Some(tz) => {
+                    let date = now.with_timezone(&tz).date_naive() + Duration::days(days);
+                    local_datetime(&tz, date, NaiveTime::MIN)
+                }
+                None => {
+                    let date = now.with_timezone(local_tz).date_naive() + Duration::days(days);
+                    local_datetime(local_tz, date, NaiveTime::MIN)
                 }
             }
-            _ => unreachable!(),
+            .ok_or(custom_error("Invalid timestamp.", span))
         }
+        _ => unreachable!(),
+    }
+}
+
+/// The output styles of systemd's `format_timestamp_style()`, as set with `--timestamp=`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimestampStyle {
+    /// `Fri 2012-11-23 11:12:13 CET`
+    #[default]
+    Pretty,
+    /// `Fri 2012-11-23 11:12:13.123456 CET`
+    Us,
+    /// `Fri 2012-11-23 10:12:13 UTC`
+    Utc,
+    /// `Fri 2012-11-23 10:12:13.123456 UTC`
+    UsUtc,
+    /// `@1353665533`
+    Unix,
+    /// `2012-11-23T11:12:13+01:00`
+    Iso8601,
+}
+
+/// Formats `timestamp` in the given style, in `tz` unless the style is in UTC.
+///
+/// Timezones are printed the way their offset displays, which is the abbreviation for [`chrono_tz::Tz`];
+/// these can only be parsed back if they are also the name of a timezone, such as `UTC`.
+pub fn format_timestamp<T: TimeZone>(
+    timestamp: &DateTime<Utc>,
+    style: TimestampStyle,
+    tz: &T,
+) -> String
+where
+    T::Offset: std::fmt::Display,
+{
+    let local = timestamp.with_timezone(tz);
+    match style {
+        TimestampStyle::Pretty => local.format("%a %Y-%m-%d %H:%M:%S %Z").to_string(),
+        TimestampStyle::Us => local.format("%a %Y-%m-%d %H:%M:%S%.6f %Z").to_string(),
+        TimestampStyle::Utc => timestamp.format("%a %Y-%m-%d %H:%M:%S UTC").to_string(),
+        TimestampStyle::UsUtc => timestamp.format("%a %Y-%m-%d %H:%M:%S%.6f UTC").to_string(),
+        TimestampStyle::Unix => format!("@{}", timestamp.timestamp()),
+        TimestampStyle::Iso8601 => local.format("%Y-%m-%dT%H:%M:%S%:z").to_string(),
     }
 }
 
+/// Formats how long ago `timestamp` was or how much time is left until it, relative to `now`, like
+/// systemd's `format_timestamp_relative()`, e.g. `3h 2min ago` or `1 week 2 days left`.
+///
+/// Only the two most significant units are shown.
+pub fn format_timestamp_relative(timestamp: &DateTime<Utc>, now: &DateTime<Utc>) -> String {
+    let (span, suffix) = match timestamp > now {
+        true => (*timestamp - *now, "left"),
+        false => (*now - *timestamp, "ago"),
+    };
+    let span = span.num_microseconds().unwrap_or(i64::MAX);
+
+    const SEC: i64 = 1_000_000;
+    const MINUTE: i64 = 60 * SEC;
+    const HOUR: i64 = 60 * MINUTE;
+    const DAY: i64 = 24 * HOUR;
+    const WEEK: i64 = 7 * DAY;
+    const MONTH: i64 = 2_629_800 * SEC;
+    const YEAR: i64 = 31_557_600 * SEC;
+    let plural = |count: i64, unit: &str| match count {
+        1 => format!("{} {}", count, unit),
+        _ => format!("{} {}s", count, unit),
+    };
+
+    let result = if span >= YEAR {
+        format!(
+            "{} {}",
+            plural(span / YEAR, "year"),
+            plural(span % YEAR / MONTH, "month")
+        )
+    } else if span >= MONTH {
+        format!(
+            "{} {}",
+            plural(span / MONTH, "month"),
+            plural(span % MONTH / DAY, "day")
+        )
+    } else if span >= WEEK {
+        format!(
+            "{} {}",
+            plural(span / WEEK, "week"),
+            plural(span % WEEK / DAY, "day")
+        )
+    } else if span >= 2 * DAY {
+        plural(span / DAY, "day")
+    } else if span >= 25 * HOUR {
+        format!("1 day {}h", (span - DAY) / HOUR)
+    } else if span >= 6 * HOUR {
+        format!("{}h", span / HOUR)
+    } else if span >= HOUR {
+        format!("{}h {}min", span / HOUR, span % HOUR / MINUTE)
+    } else if span >= 5 * MINUTE {
+        format!("{}min", span / MINUTE)
+    } else if span >= MINUTE {
+        format!("{}min {}s", span / MINUTE, span % MINUTE / SEC)
+    } else if span >= SEC {
+        format!("{}s", span / SEC)
+    } else if span >= 1000 {
+        format!("{}ms", span / 1000)
+    } else if span > 0 {
+        format!("{}us", span)
+    } else {
+        return "now".to_string();
+    };
+    format!("{} {}", result, suffix)
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::config::UnitEntry;
-    use chrono::{DateTime, TimeZone, Timelike, Utc};
+    use crate::datetime::{
+        format_timestamp, format_timestamp_relative, parse_timestamp_at, TimestampStyle,
+    };
+    use chrono::{DateTime, Duration, TimeZone, Utc};
+    use chrono_tz::{America::New_York, Asia::Shanghai, UTC};
 
-    fn test_pairs(pair: &Vec<(&str, DateTime<Utc>)>) {
-        for each in pair {
-            let parse: DateTime<Utc> = UnitEntry::parse_from_str(each.0).unwrap();
-            assert_eq!(parse, each.1);
-            println!("{} passed.", each.0);
+    fn test_pairs<T: TimeZone>(now: DateTime<Utc>, tz: &T, pairs: &Vec<(&str, DateTime<Utc>)>) {
+        for each in pairs {
+            let parse = parse_timestamp_at(each.0, now, tz).unwrap();
+            assert_eq!(parse, each.1, "{}", each.0);
         }
     }
 
     #[test]
     fn test_datetime() {
+        let now = Utc.with_ymd_and_hms(2012, 11, 23, 18, 15, 22).unwrap();
         let pairs = vec![
             (
                 "Fri 2012-11-23 11:12:13",
@@ -262,6 +332,11 @@ mod tests {
                 "2012-11-23 11:12:13 UTC",
                 Utc.with_ymd_and_hms(2012, 11, 23, 11, 12, 13).unwrap(),
             ),
+            (
+                "2012-11-23 11:12:13.5",
+                Utc.with_ymd_and_hms(2012, 11, 23, 11, 12, 13).unwrap()
+                    + Duration::milliseconds(500),
+            ),
             (
                 "2012-11-23",
                 Utc.with_ymd_and_hms(2012, 11, 23, 0, 0, 0).unwrap(),
@@ -272,53 +347,161 @@ mod tests {
             ),
             (
                 "11:12:13",
-                Utc::now()
-                    .with_hour(11)
-                    .unwrap()
-                    .with_minute(12)
-                    .unwrap()
-                    .with_second(13)
-                    .unwrap()
-                    .with_nanosecond(0)
-                    .unwrap(),
+                Utc.with_ymd_and_hms(2012, 11, 23, 11, 12, 13).unwrap(),
             ),
             (
                 "11:12",
-                Utc::now()
-                    .with_hour(11)
-                    .unwrap()
-                    .with_minute(12)
-                    .unwrap()
-                    .with_second(0)
-                    .unwrap()
-                    .with_nanosecond(0)
-                    .unwrap(),
+                Utc.with_ymd_and_hms(2012, 11, 23, 11, 12, 0).unwrap(),
             ),
-            // cannot be tested due to processing time
-            //             ("now", Utc::now()),
+            ("now", now),
             (
                 "today",
-                Utc::now()
-                    .with_hour(0)
-                    .unwrap()
-                    .with_minute(0)
-                    .unwrap()
-                    .with_second(0)
-                    .unwrap()
-                    .with_nanosecond(0)
-                    .unwrap(),
+                Utc.with_ymd_and_hms(2012, 11, 23, 0, 0, 0).unwrap(),
+            ),
+            (
+                "yesterday",
+                Utc.with_ymd_and_hms(2012, 11, 22, 0, 0, 0).unwrap(),
+            ),
+            (
+                "tomorrow",
+                Utc.with_ymd_and_hms(2012, 11, 24, 0, 0, 0).unwrap(),
             ),
-            // cannot be tested due to processing time
-            //            ("+3h30min", Utc::now() + Duration::minutes(3 * 60 + 30)),
-            //            ("-5s", Utc::now() - Duration::seconds(5)),
-            //            ("11min ago", Utc::now() - Duration::minutes(11)),
+            ("epoch", Utc.with_ymd_and_hms(1970, 1, 1, 0, 0, 0).unwrap()),
+            ("+3h30min", now + Duration::minutes(3 * 60 + 30)),
+            ("-5s", now - Duration::seconds(5)),
+            ("11min ago", now - Duration::minutes(11)),
+            ("1.5h left", now + Duration::minutes(90)),
             (
                 "@1395716396",
                 // different from systemd examples
                 Utc.with_ymd_and_hms(2014, 3, 25, 2, 59, 56).unwrap(),
             ),
         ];
+        test_pairs(now, &UTC, &pairs);
 
-        test_pairs(&pairs);
+        for input in [
+            "Sat 2012-11-23",
+            "2012-13-01",
+            "today Mars/Olympus",
+            "+infinity",
+            "-",
+        ] {
+            assert!(parse_timestamp_at(input, now, &UTC).is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_timezones() {
+        // already the 24th in Shanghai
+        let now = Utc.with_ymd_and_hms(2012, 11, 23, 18, 15, 22).unwrap();
+        let pairs = vec![
+            (
+                "today",
+                Utc.with_ymd_and_hms(2012, 11, 23, 16, 0, 0).unwrap(),
+            ),
+            (
+                "11:12",
+                Utc.with_ymd_and_hms(2012, 11, 24, 3, 12, 0).unwrap(),
+            ),
+            (
+                "2012-11-23 11:12:13",
+                Utc.with_ymd_and_hms(2012, 11, 23, 3, 12, 13).unwrap(),
+            ),
+            (
+                "tomorrow UTC",
+                Utc.with_ymd_and_hms(2012, 11, 24, 0, 0, 0).unwrap(),
+            ),
+            (
+                "11:12 America/New_York",
+                Utc.with_ymd_and_hms(2012, 11, 23, 16, 12, 0).unwrap(),
+            ),
+        ];
+        test_pairs(now, &Shanghai, &pairs);
+
+        // the day before DST ends has 25 hours
+        let now = Utc.with_ymd_and_hms(2012, 11, 4, 12, 0, 0).unwrap();
+        let pairs = vec![
+            (
+                "yesterday",
+                Utc.with_ymd_and_hms(2012, 11, 3, 4, 0, 0).unwrap(),
+            ),
+            ("today", Utc.with_ymd_and_hms(2012, 11, 4, 4, 0, 0).unwrap()),
+            (
+                "tomorrow",
+                Utc.with_ymd_and_hms(2012, 11, 5, 5, 0, 0).unwrap(),
+            ),
+        ];
+        test_pairs(now, &New_York, &pairs);
+    }
+
+    #[test]
+    fn test_format() {
+        let timestamp = Utc.with_ymd_and_hms(2012, 11, 23, 10, 12, 13).unwrap()
+            + Duration::microseconds(123456);
+        let pairs = [
+            (TimestampStyle::Pretty, "Fri 2012-11-23 18:12:13 CST"),
+            (TimestampStyle::Us, "Fri 2012-11-23 18:12:13.123456 CST"),
+            (TimestampStyle::Utc, "Fri 2012-11-23 10:12:13 UTC"),
+            (TimestampStyle::UsUtc, "Fri 2012-11-23 10:12:13.123456 UTC"),
+            (TimestampStyle::Unix, "@1353665533"),
+            (TimestampStyle::Iso8601, "2012-11-23T18:12:13+08:00"),
+        ];
+        for (style, output) in pairs {
+            assert_eq!(format_timestamp(&timestamp, style, &Shanghai), output);
+        }
+
+        // round trips, down to the precision of each style
+        let now = Utc.with_ymd_and_hms(2013, 1, 1, 0, 0, 0).unwrap();
+        let seconds = Utc.with_ymd_and_hms(2012, 11, 23, 10, 12, 13).unwrap();
+        for (style, expected) in [
+            (TimestampStyle::Pretty, seconds),
+            (TimestampStyle::Us, timestamp),
+            (TimestampStyle::Utc, seconds),
+            (TimestampStyle::UsUtc, timestamp),
+            (TimestampStyle::Unix, seconds),
+        ] {
+            let formatted = format_timestamp(&timestamp, style, &UTC);
+            assert_eq!(
+                parse_timestamp_at(&formatted, now, &Shanghai).unwrap(),
+                expected,
+                "{}",
+                formatted
+            );
+        }
+        let local = format_timestamp(&timestamp, TimestampStyle::Pretty, &Shanghai);
+        let local = local.trim_end_matches(" CST");
+        assert_eq!(parse_timestamp_at(local, now, &Shanghai).unwrap(), seconds);
+    }
+
+    #[test]
+    fn test_format_relative() {
+        let now = Utc.with_ymd_and_hms(2012, 11, 23, 18, 15, 22).unwrap();
+        let pairs = [
+            (
+                Duration::minutes(3 * 60 + 2) + Duration::seconds(5),
+                "3h 2min ago",
+            ),
+            (Duration::days(400), "1 year 1 month ago"),
+            (Duration::days(45), "1 month 14 days ago"),
+            (Duration::days(9), "1 week 2 days ago"),
+            (Duration::days(3), "3 days ago"),
+            (Duration::hours(30), "1 day 6h ago"),
+            (Duration::hours(7), "7h ago"),
+            (Duration::minutes(7), "7min ago"),
+            (Duration::seconds(90), "1min 30s ago"),
+            (Duration::seconds(5), "5s ago"),
+            (Duration::milliseconds(5), "5ms ago"),
+            (Duration::microseconds(5), "5us ago"),
+            (Duration::zero(), "now"),
+            (Duration::seconds(-90), "1min 30s left"),
+        ];
+        for (span, output) in pairs {
+            let timestamp = now - span;
+            let formatted = format_timestamp_relative(&timestamp, &now);
+            assert_eq!(formatted, output);
+            // the output is truncated to the units shown, so it stays the same after a round trip
+            let parsed = parse_timestamp_at(&formatted, now, &UTC).unwrap();
+            assert_eq!(format_timestamp_relative(&parsed, &now), output);
+        }
     }
 }
@@ -1,56 +1,239 @@
-use crate::{
-    config::UnitEntry,
-    datetime::{DatetimeParser, Rule},
-};
+use crate::{config::UnitEntry, error::Error, extract_word::WHITESPACE};
 use chrono::Duration;
-use pest::{iterators::Pairs, Parser};
+use std::fmt;
+
+const NSEC_PER_USEC: u64 = 1_000;
+const NSEC_PER_MSEC: u64 = 1_000_000;
+const NSEC_PER_SEC: u64 = 1_000_000_000;
+const NSEC_PER_MINUTE: u64 = 60 * NSEC_PER_SEC;
+const NSEC_PER_HOUR: u64 = 60 * NSEC_PER_MINUTE;
+const NSEC_PER_DAY: u64 = 24 * NSEC_PER_HOUR;
+const NSEC_PER_WEEK: u64 = 7 * NSEC_PER_DAY;
+// an average month and year of the Gregorian calendar, 30.44 and 365.25 days
+const NSEC_PER_MONTH: u64 = 2_629_800 * NSEC_PER_SEC;
+const NSEC_PER_YEAR: u64 = 31_557_600 * NSEC_PER_SEC;
+
+// matched by prefix in this order, like systemd's `extract_multiplier()`
+const UNITS: [(&str, u64); 32] = [
+    ("seconds", NSEC_PER_SEC),
+    ("second", NSEC_PER_SEC),
+    ("sec", NSEC_PER_SEC),
+    ("s", NSEC_PER_SEC),
+    ("minutes", NSEC_PER_MINUTE),
+    ("minute", NSEC_PER_MINUTE),
+    ("min", NSEC_PER_MINUTE),
+    ("months", NSEC_PER_MONTH),
+    ("month", NSEC_PER_MONTH),
+    ("M", NSEC_PER_MONTH),
+    ("msec", NSEC_PER_MSEC),
+    ("ms", NSEC_PER_MSEC),
+    ("m", NSEC_PER_MINUTE),
+    ("hours", NSEC_PER_HOUR),
+    ("hour", NSEC_PER_HOUR),
+    ("hr", NSEC_PER_HOUR),
+    ("h", NSEC_PER_HOUR),
+    ("days", NSEC_PER_DAY),
+    ("day", NSEC_PER_DAY),
+    ("d", NSEC_PER_DAY),
+    ("weeks", NSEC_PER_WEEK),
+    ("week", NSEC_PER_WEEK),
+    ("w", NSEC_PER_WEEK),
+    ("years", NSEC_PER_YEAR),
+    ("year", NSEC_PER_YEAR),
+    ("y", NSEC_PER_YEAR),
+    ("usec", NSEC_PER_USEC),
+    ("us", NSEC_PER_USEC),
+    ("μs", NSEC_PER_USEC),
+    ("µs", NSEC_PER_USEC),
+    ("nsec", 1),
+    ("ns", 1),
+];
+
+// the units used when formatting, like systemd's `format_timespan()`
+const FORMAT_UNITS: [(&str, u64); 9] = [
+    ("y", NSEC_PER_YEAR),
+    ("month", NSEC_PER_MONTH),
+    ("w", NSEC_PER_WEEK),
+    ("d", NSEC_PER_DAY),
+    ("h", NSEC_PER_HOUR),
+    ("min", NSEC_PER_MINUTE),
+    ("s", NSEC_PER_SEC),
+    ("ms", NSEC_PER_MSEC),
+    ("us", NSEC_PER_USEC),
+];
+
+/// A time span such as `TimeoutStartSec=` or `RestartSec=`, which may also be `infinity`.
+///
+/// Spans are parsed like systemd's `parse_sec()`: numbers may have fractions (`1.5h`, `0.5s`) and are
+/// followed by any of the unit spellings in systemd.time (`hr`, `minutes`, `μs`, `nsec`...) or by nothing,
+/// in which case they are seconds. Multiple parts are added up, negative spans are rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TimeSpan {
+    Finite(Duration),
+    Infinity,
+}
+
+impl UnitEntry for TimeSpan {
+    type Error = Error;
+    // spans may contain whitespace (`1h 30min`), so `multiple` entries take whole assignments
+    const SPLIT: bool = false;
+    fn parse_from_str<S: AsRef<str>>(input: S) -> std::result::Result<Self, Self::Error> {
+        parse_timespan(input.as_ref(), NSEC_PER_SEC)
+    }
+}
 
 impl UnitEntry for Duration {
-    type Error = pest::error::Error<Rule>;
+    type Error = Error;
+    const SPLIT: bool = false;
     fn parse_from_str<S: AsRef<str>>(input: S) -> std::result::Result<Self, Self::Error> {
-        let parse = DatetimeParser::parse(Rule::timespan, input.as_ref())?;
-        duration_from_parser(parse)
+        match TimeSpan::parse_from_str(input.as_ref())? {
+            TimeSpan::Finite(duration) => Ok(duration),
+            TimeSpan::Infinity => Err(Error::InvalidTimeSpanError {
+                timespan: input.as_ref().to_string(),
+            }),
+        }
     }
 }
 
-pub(crate) fn duration_from_parser(
-    mut parse: Pairs<'_, Rule>,
-) -> std::result::Result<Duration, pest::error::Error<Rule>> {
-    let timespan = parse.next().unwrap().into_inner();
-    let mut result = Duration::zero();
-    for segment in timespan {
-        if segment.as_rule() == Rule::segment {
-            let mut inner = segment.into_inner();
-            let number: i64 = inner.next().unwrap().as_str().parse().unwrap();
-            let unit = inner.next().unwrap();
-            let addition = match unit.as_rule() {
-                Rule::usec => Duration::microseconds(number),
-                Rule::msec => Duration::milliseconds(number),
-                Rule::seconds => Duration::seconds(number),
-                Rule::minutes => Duration::minutes(number),
-                Rule::hours => Duration::hours(number),
-                Rule::days => Duration::days(number),
-                Rule::weeks => Duration::weeks(number),
-                Rule::months => {
-                    Duration::seconds(/* 30.44 * 24 * 60 * 60 = */ 2630016 * number)
+/// Parses a time span in which numbers without a unit are multiples of `default_unit` nanoseconds.
+pub(crate) fn parse_timespan(input: &str, default_unit: u64) -> Result<TimeSpan, Error> {
+    let error = || Error::InvalidTimeSpanError {
+        timespan: input.to_string(),
+    };
+    let mut rest = input.trim_matches(|x| WHITESPACE.contains(x));
+    if rest == "infinity" {
+        return Ok(TimeSpan::Infinity);
+    }
+    if rest.is_empty() {
+        return Err(error());
+    }
+
+    let mut result: u128 = 0;
+    while !rest.is_empty() {
+        let integer_len = rest
+            .find(|x: char| !x.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let (integer, after) = rest.split_at(integer_len);
+        let (fraction, after) = match after.strip_prefix('.') {
+            Some(after) => after.split_at(
+                after
+                    .find(|x: char| !x.is_ascii_digit())
+                    .unwrap_or(after.len()),
+            ),
+            None => ("", after),
+        };
+        if integer.is_empty() && fraction.is_empty() {
+            return Err(error());
+        }
+        let trimmed = after.trim_start_matches(|x| WHITESPACE.contains(x));
+        let (multiplier, after) = match UNITS.iter().find(|(unit, _)| trimmed.starts_with(unit)) {
+            Some((unit, multiplier)) => (*multiplier, &trimmed[unit.len()..]),
+            // like systemd, accept `12.34 .56` but not `12.34.56`
+            None if after.is_empty() || after.len() != trimmed.len() => (default_unit, trimmed),
+            None => return Err(error()),
+        };
+
+        let integer: u128 = match integer {
+            "" => 0,
+            integer => integer.parse().map_err(|_| error())?,
+        };
+        // fractions of nanoseconds are dropped
+        let fraction = fraction
+            .bytes()
+            .take(18)
+            .enumerate()
+            .fold(0u128, |acc, (i, x)| {
+                acc + (x - b'0') as u128 * multiplier as u128 * 10u128.pow(17 - i as u32)
+            })
+            / 10u128.pow(18);
+        result = integer
+            .checked_mul(multiplier as u128)
+            .and_then(|x| x.checked_add(fraction))
+            .and_then(|x| x.checked_add(result))
+            .filter(|x| *x <= u64::MAX as u128 * NSEC_PER_SEC as u128)
+            .ok_or_else(error)?;
+        rest = after.trim_start_matches(|x| WHITESPACE.contains(x));
+    }
+
+    let result = std::time::Duration::new(
+        (result / NSEC_PER_SEC as u128) as u64,
+        (result % NSEC_PER_SEC as u128) as u32,
+    );
+    Duration::from_std(result)
+        .map(TimeSpan::Finite)
+        .map_err(|_| error())
+}
+
+impl TimeSpan {
+    /// Formats the span like systemd's `format_timespan()`, dropping parts smaller than `accuracy`.
+    ///
+    /// Spans shorter than a minute that don't fit a single unit are written with decimals, such as
+    /// `1.500000s`, while longer ones are split into units, such as `1h 30min`.
+    pub fn format(&self, accuracy: Duration) -> String {
+        let duration = match self {
+            TimeSpan::Infinity => return "infinity".to_string(),
+            TimeSpan::Finite(duration) => *duration,
+        };
+        let mut span = match duration.to_std() {
+            Ok(duration) if !duration.is_zero() => duration.as_nanos(),
+            _ => return "0".to_string(),
+        };
+        let accuracy = accuracy
+            .to_std()
+            .map_or(0, |x| x.as_nanos())
+            .max(NSEC_PER_USEC as u128);
+
+        let mut result = String::new();
+        for (suffix, unit) in FORMAT_UNITS {
+            let unit = unit as u128;
+            if span == 0 || (span < accuracy && !result.is_empty()) {
+                break;
+            }
+            if span < unit {
+                continue;
+            }
+            if !result.is_empty() {
+                result.push(' ');
+            }
+            let (whole, remainder) = (span / unit, span % unit);
+
+            if span < NSEC_PER_MINUTE as u128 && remainder > 0 {
+                // as many decimals as are significant with the given accuracy
+                let decimals = unit.ilog10() as i32 - accuracy.ilog10() as i32;
+                if decimals > 0 {
+                    let remainder = remainder / 10u128.pow(unit.ilog10() - decimals as u32);
+                    result.push_str(&format!(
+                        "{}.{:0width$}{}",
+                        whole,
+                        remainder,
+                        suffix,
+                        width = decimals as usize
+                    ));
+                    break;
                 }
-                Rule::years => Duration::hours(/* 365.25 * 24 = */ 8766 * number),
-                _ => unreachable!(),
-            };
-            result = result + addition;
-        } else {
-            let number: i64 = segment.as_str().parse().unwrap();
-            result = result + Duration::seconds(number);
+            }
+            result.push_str(&format!("{}{}", whole, suffix));
+            span = remainder;
+        }
+        match result.is_empty() {
+            true => "0".to_string(),
+            false => result,
         }
     }
-    Ok(result)
+}
+
+impl fmt::Display for TimeSpan {
+    /// Formats the span with an accuracy of one microsecond, like `systemd-analyze timespan`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.format(Duration::microseconds(1)))
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use chrono::Duration;
 
-    use crate::config::UnitEntry;
+    use crate::{config::UnitEntry, duration::TimeSpan};
 
     fn test_pairs(pair: &Vec<(&str, Duration)>) {
         for each in pair {
@@ -87,4 +270,54 @@ mod tests {
         let target = Duration::seconds(114);
         assert_eq!(parse, target);
     }
+
+    #[test]
+    fn timespan() {
+        let pairs = vec![
+            ("1.5h", Duration::minutes(90)),
+            ("0.5s", Duration::milliseconds(500)),
+            (".5s", Duration::milliseconds(500)),
+            ("2hr 3 minutes", Duration::minutes(123)),
+            ("1μs 1µs 1usec", Duration::microseconds(3)),
+            ("10nsec", Duration::nanoseconds(10)),
+            ("1M", Duration::seconds(2629800)),
+            ("1y", Duration::hours(8766)),
+            ("5 5", Duration::seconds(10)),
+        ];
+        for (input, duration) in pairs {
+            assert_eq!(
+                TimeSpan::parse_from_str(input).unwrap(),
+                TimeSpan::Finite(duration)
+            );
+        }
+        assert_eq!(
+            TimeSpan::parse_from_str(" infinity ").unwrap(),
+            TimeSpan::Infinity
+        );
+        for input in ["", "-5s", "5mins", "5x", "1.2.3s", "infinity 5s"] {
+            assert!(TimeSpan::parse_from_str(input).is_err(), "{}", input);
+        }
+        assert!(Duration::parse_from_str("infinity").is_err());
+    }
+
+    #[test]
+    fn timespan_format() {
+        let pairs = vec![
+            ("1.5h", "1h 30min"),
+            ("1.5s", "1.500000s"),
+            ("500ms", "500ms"),
+            ("1.5ms", "1.500ms"),
+            ("2d 3h 4min 5s", "2d 3h 4min 5s"),
+            ("1min 5.006s", "1min 5.006000s"),
+            ("1y 1M", "1y 1month"),
+            ("0", "0"),
+            ("10ns", "0"),
+            ("infinity", "infinity"),
+        ];
+        for (input, output) in pairs {
+            assert_eq!(TimeSpan::parse_from_str(input).unwrap().to_string(), output);
+        }
+        let span = TimeSpan::parse_from_str("1h 2min 3.5s").unwrap();
+        assert_eq!(span.format(Duration::minutes(1)), "1h 2min");
+    }
 }
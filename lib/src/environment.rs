@@ -0,0 +1,291 @@
+use crate::{
+    config::{Result, UnitEntry},
+    error::{Error, ReadFileSnafu},
+    exec::is_valid_env_name,
+    extract_word::{split_words, WHITESPACE},
+    fs::UnitFs,
+};
+use snafu::ResultExt;
+use std::{collections::HashMap, io, path::PathBuf};
+
+/// The variables assigned by an `Environment=` line, in order.
+///
+/// Assignments are quoted like command lines (`"FOO=a b" BAR=c`); those that are not `KEY=VALUE` with a
+/// valid variable name are skipped. Use with `multiple` to collect every line.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct EnvironmentAssignments(pub Vec<(String, String)>);
+
+impl UnitEntry for EnvironmentAssignments {
+    type Error = Error;
+    const SPLIT: bool = false;
+    fn parse_from_str<S: AsRef<str>>(input: S) -> std::result::Result<Self, Self::Error> {
+        let mut result = Vec::new();
+        for word in split_words(input.as_ref(), WHITESPACE, false)? {
+            match word.split_once('=') {
+                Some((key, value)) if is_valid_env_name(key) => {
+                    result.push((key.to_string(), value.to_string()))
+                }
+                _ => log::warn!("Invalid environment assignment {}, ignoring.", word),
+            }
+        }
+        Ok(Self(result))
+    }
+}
+
+/// A file referenced by `EnvironmentFile=`, which is `optional` if prefixed with `-`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnvironmentFile {
+    pub path: PathBuf,
+    pub optional: bool,
+}
+
+impl UnitEntry for EnvironmentFile {
+    type Error = Error;
+    const SPLIT: bool = false;
+    fn parse_from_str<S: AsRef<str>>(input: S) -> std::result::Result<Self, Self::Error> {
+        let input = input.as_ref();
+        let (path, optional) = match input.strip_prefix('-') {
+            Some(path) => (path, true),
+            None => (input, false),
+        };
+        if !path.starts_with('/') {
+            return Err(Error::InvalidValueError {
+                value: input.to_string(),
+                source: "Environment files should be absolute paths.".into(),
+            });
+        }
+        Ok(Self {
+            path: PathBuf::from(path),
+            optional,
+        })
+    }
+}
+
+impl EnvironmentFile {
+    /// Reads the variables assigned in the file, see [`parse_env_file`]. Missing optional files are
+    /// treated as empty.
+    pub fn load(&self, fs: &dyn UnitFs) -> Result<Vec<(String, String)>> {
+        match fs.read_to_string(&self.path) {
+            Err(err) if self.optional && err.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+            content => Ok(parse_env_file(&content.context(ReadFileSnafu {
+                path: self.path.to_string_lossy().to_string(),
+            })?)),
+        }
+    }
+}
+
+/// Merges `Environment=` and `EnvironmentFile=` into the environment of a command.
+///
+/// Like in systemd, the assignments are applied in declaration order first, followed by the files, so
+/// variables set in files take precedence.
+pub fn merge_environment(
+    assignments: &[EnvironmentAssignments],
+    files: &[EnvironmentFile],
+    fs: &dyn UnitFs,
+) -> Result<HashMap<String, String>> {
+    let mut result = HashMap::new();
+    for assignment in assignments.iter() {
+        result.extend(assignment.0.iter().cloned());
+    }
+    for file in files.iter() {
+        result.extend(file.load(fs)?);
+    }
+    Ok(result)
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    PreKey,
+    Key,
+    PreValue,
+    Value,
+    ValueEscape,
+    SingleQuoteValue,
+    DoubleQuoteValue,
+    DoubleQuoteValueEscape,
+    Comment,
+    CommentEscape,
+}
+
+/// Parses an environment file the way systemd's `parse_env_file()` does.
+///
+/// Lines starting with `#` or `;` are comments, and a leading `export` is ignored. Values may be quoted
+/// with single quotes (taken literally) or double quotes (where `\"`, `\\`, `` \` `` and `\$` are
+/// unescaped), and lines ending with a backslash continue on the next line. Lines without `=` and
+/// assignments to invalid variable names are skipped.
+pub fn parse_env_file(input: &str) -> Vec<(String, String)> {
+    let mut result = Vec::new();
+    let mut state = State::PreKey;
+    let mut key = String::new();
+    let mut value = String::new();
+    // trailing whitespace is trimmed from keys and unquoted values
+    let mut value_trim = None;
+
+    let mut push = |key: &mut String, value: &mut String, trim: Option<usize>| {
+        if let Some(trim) = trim {
+            value.truncate(trim);
+        }
+        let mut name = key.trim_end_matches(|x| WHITESPACE.contains(x));
+        if let Some(rest) = name.strip_prefix("export") {
+            if rest.starts_with(|x| WHITESPACE.contains(x)) {
+                name = rest.trim_start_matches(|x| WHITESPACE.contains(x));
+            }
+        }
+        match is_valid_env_name(name) {
+            true => result.push((name.to_string(), std::mem::take(value))),
+            false => log::warn!(
+                "Invalid variable name {} in environment file, ignoring.",
+                name
+            ),
+        }
+        key.clear();
+        value.clear();
+    };
+
+    for char in input.chars() {
+        let newline = char == '\n' || char == '\r';
+        state = match state {
+            State::PreKey if char == '#' || char == ';' => State::Comment,
+            State::PreKey if WHITESPACE.contains(char) => State::PreKey,
+            State::PreKey | State::Key if char == '=' => {
+                value_trim = None;
+                State::PreValue
+            }
+            // a line without an assignment
+            State::PreKey | State::Key if newline => {
+                key.clear();
+                State::PreKey
+            }
+            State::PreKey | State::Key => {
+                key.push(char);
+                State::Key
+            }
+            State::PreValue | State::Value if newline => {
+                push(&mut key, &mut value, value_trim.take());
+                State::PreKey
+            }
+            State::PreValue if char == '\'' => State::SingleQuoteValue,
+            State::PreValue if char == '"' => State::DoubleQuoteValue,
+            State::PreValue if WHITESPACE.contains(char) => State::PreValue,
+            State::PreValue | State::Value if char == '\\' => {
+                value_trim = None;
+                State::ValueEscape
+            }
+            State::PreValue | State::Value => {
+                match WHITESPACE.contains(char) {
+                    true => {
+                        value_trim.get_or_insert(value.len());
+                    }
+                    false => value_trim = None,
+                }
+                value.push(char);
+                State::Value
+            }
+            // escaped newlines continue the value
+            State::ValueEscape => {
+                if !newline {
+                    value.push(char);
+                }
+                State::Value
+            }
+            State::SingleQuoteValue if char == '\'' => State::PreValue,
+            State::SingleQuoteValue => {
+                value.push(char);
+                State::SingleQuoteValue
+            }
+            State::DoubleQuoteValue if char == '"' => State::PreValue,
+            State::DoubleQuoteValue if char == '\\' => State::DoubleQuoteValueEscape,
+            State::DoubleQuoteValue => {
+                value.push(char);
+                State::DoubleQuoteValue
+            }
+            State::DoubleQuoteValueEscape => {
+                if "\"\\`$".contains(char) {
+                    value.push(char);
+                } else if char != '\n' {
+                    value.push('\\');
+                    value.push(char);
+                }
+                State::DoubleQuoteValue
+            }
+            State::Comment if char == '\\' => State::CommentEscape,
+            State::Comment if newline => State::PreKey,
+            State::Comment | State::CommentEscape => State::Comment,
+        };
+    }
+
+    if !matches!(
+        state,
+        State::PreKey | State::Key | State::Comment | State::CommentEscape
+    ) {
+        push(&mut key, &mut value, value_trim);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        config::UnitEntry,
+        environment::{merge_environment, parse_env_file, EnvironmentAssignments, EnvironmentFile},
+        fs::MemoryFs,
+    };
+    use std::path::Path;
+
+    fn pairs(input: &[(&str, &str)]) -> Vec<(String, String)> {
+        input
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_assignments() {
+        let assignments =
+            EnvironmentAssignments::parse_from_str(r#"FOO=1 "BAR=a b" EMPTY= 1NVALID=x noassign"#)
+                .unwrap();
+        assert_eq!(
+            assignments.0,
+            pairs(&[("FOO", "1"), ("BAR", "a b"), ("EMPTY", "")])
+        );
+    }
+
+    #[test]
+    fn test_env_file() {
+        let input = "# comment \\\nstill=comment\n; other comment\n  export FOO = bar baz  \nSINGLE='a \\\"b' c\nDOUBLE=\"x \\\"y\\\" \\z \\$\"\nCONT=first\\\nsecond\nnoassign\nEND=last";
+        assert_eq!(
+            parse_env_file(input),
+            pairs(&[
+                ("FOO", "bar baz"),
+                ("SINGLE", "a \\\"bc"),
+                ("DOUBLE", "x \"y\" \\z $"),
+                ("CONT", "firstsecond"),
+                ("END", "last"),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut fs = MemoryFs::new();
+        fs.add_file("/etc/default/foo", "A=file\nC=3\n");
+
+        let file = EnvironmentFile::parse_from_str("-/etc/default/foo").unwrap();
+        assert!(file.optional);
+        assert_eq!(file.path, Path::new("/etc/default/foo"));
+        assert!(EnvironmentFile::parse_from_str("relative").is_err());
+
+        let assignments = vec![
+            EnvironmentAssignments::parse_from_str("A=1 B=2").unwrap(),
+            EnvironmentAssignments::parse_from_str("B=3").unwrap(),
+        ];
+        let missing = EnvironmentFile::parse_from_str("-/missing").unwrap();
+        let env = merge_environment(&assignments, &[file, missing], &fs).unwrap();
+        assert_eq!(env["A"], "file");
+        assert_eq!(env["B"], "3");
+        assert_eq!(env["C"], "3");
+
+        let required = EnvironmentFile::parse_from_str("/missing").unwrap();
+        assert!(merge_environment(&[], &[required], &fs).is_err());
+    }
+}
@@ -1,10 +1,7 @@
 use snafu::Snafu;
 
-use crate::parser::Rule;
 use std::io;
 
-type RuleError = pest::error::Error<Rule>;
-
 // TODO: change errors to `log::warn`s to prevent one bad file from stalling the entire loading process
 
 #[derive(Debug, Snafu)]
@@ -34,42 +31,69 @@ pub enum Error {
     #[snafu(display("Invalid filename {}.", filename))]
     InvalidFilenameError { filename: String },
 
-    #[snafu(display("Failed to parse input: {}.", source))]
-    ParsingError { source: RuleError },
+    #[snafu(display("Failed to parse input at line {}: {}.", line, reason))]
+    ParsingError { line: usize, reason: &'static str },
 
     #[snafu(display("Unit file should provide at least one section."))]
     NoSectionError,
 
-    #[snafu(display("Expecting section but found {:?}.", actual))]
-    SectionError { actual: Rule },
-
-    #[snafu(display("Expecting section name but found {:?}.", actual))]
-    SectionNameError { actual: Rule },
-
     #[snafu(display("Failed to parse section {}.", key))]
     SectionParsingError { key: String },
 
-    #[snafu(display("Expecting entry but found {:?}.", actual))]
-    EntryError { actual: Rule },
-
-    #[snafu(display("Expecting entry key but found {:?}.", actual))]
-    EntryKeyError { actual: Rule },
-
-    #[snafu(display("Expecting entry value but found {:?}.", actual))]
-    EntryValueError { actual: Rule },
-
     #[snafu(display("Missing entry with key {}, which is required.", key))]
     EntryMissingError { key: String },
 
     #[snafu(display("Missing section with key {}, which is required.", key))]
     SectionMissingError { key: String },
 
-    #[snafu(display("Failed to parse {} as the value of entry with key {}.", value, key))]
-    ValueParsingError { key: String, value: String },
+    #[snafu(display(
+        "Failed to parse {} as the value of entry with key {}: {}",
+        value,
+        key,
+        source
+    ))]
+    ValueParsingError {
+        key: String,
+        value: String,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+
+    #[snafu(display("Invalid value {}: {}", value, source))]
+    InvalidValueError {
+        value: String,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+
+    #[snafu(display("Unknown value {}, expected one of {}.", value, expected))]
+    UnknownValueError { value: String, expected: String },
+
+    #[snafu(display(
+        "{} cannot be borrowed from the unit file, as it was rewritten while parsing.",
+        value
+    ))]
+    BorrowValueError { value: String },
 
     #[snafu(display("Failed to find unit {}.", name))]
     NoUnitFoundError { name: String },
 
+    #[snafu(display("{} is included recursively.", path))]
+    IncludeCycleError { path: String },
+
+    #[snafu(display("Invalid command line {}: {}.", command, reason))]
+    InvalidCommandError {
+        command: String,
+        reason: &'static str,
+    },
+
+    #[snafu(display("Invalid size {}.", size))]
+    InvalidSizeError { size: String },
+
+    #[snafu(display("Invalid time span {}.", timespan))]
+    InvalidTimeSpanError { timespan: String },
+
+    #[snafu(display("Unbalanced quotes or trailing escape in {}.", input))]
+    WordExtractionError { input: String },
+
     #[snafu(display("Invalid specifier: {}", specifier))]
     InvalidSpecifierError { specifier: char },
 }
@@ -25,10 +25,12 @@ pub(crate) fn escape<S: AsRef<str>>(input: S) -> String {
     result
 }
 
+#[allow(dead_code)]
 pub(crate) fn unescape_path<S: AsRef<str>>(input: S) -> String {
     format!("/{}", input.as_ref().replace('-', "/"))
 }
 
+#[allow(dead_code)]
 pub(crate) fn unescape_non_path<S: AsRef<str>>(input: S) -> String {
     input.as_ref().replace('-', "/")
 }
@@ -0,0 +1,255 @@
+use crate::{
+    config::UnitEntry,
+    error::Error,
+    extract_word::{extract_first_word, split_words, WHITESPACE},
+};
+use std::collections::HashMap;
+
+/// A command line of `ExecStart=` and similar keys.
+///
+/// `%` specifiers are resolved when parsing the unit, while environment variables are only expanded by
+/// [`ExecCommand::expand`], once the environment of the command is known. As each assignment is a single
+/// command, `multiple` entries of `ExecCommand`s are not split into words.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecCommand {
+    pub path: String,
+    /// The arguments of the command, including `argv[0]`, which differs from `path` with the `@` prefix.
+    pub argv: Vec<String>,
+    /// `-`: the exit code of the command is ignored.
+    pub ignore_failure: bool,
+    /// `:`: environment variables are not expanded.
+    pub no_env_expand: bool,
+    pub privileges: ExecPrivileges,
+}
+
+/// The privileges a command is run with, set with the `+`, `!` and `!!` prefixes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExecPrivileges {
+    #[default]
+    Default,
+    /// `+`: runs with full privileges, ignoring `User=`, sandboxing and so on.
+    Full,
+    /// `!`: runs with elevated privileges, but still applies `User=` and `Group=`.
+    NoSetuid,
+    /// `!!`: like `!`, but only on systems lacking support for ambient capabilities.
+    AmbientMagic,
+}
+
+impl UnitEntry for ExecCommand {
+    type Error = Error;
+    const SPLIT: bool = false;
+    fn parse_from_str<S: AsRef<str>>(input: S) -> std::result::Result<Self, Self::Error> {
+        let input = input.as_ref();
+        let error = |reason| Error::InvalidCommandError {
+            command: input.to_string(),
+            reason,
+        };
+
+        // each prefix may appear once, and `+`, `!` and `!!` exclude each other
+        let mut rest = input.trim_start_matches(|x| WHITESPACE.contains(x));
+        let mut separate_argv0 = false;
+        let mut ignore_failure = false;
+        let mut no_env_expand = false;
+        let mut privileges = ExecPrivileges::Default;
+        loop {
+            match (rest.as_bytes().first(), privileges) {
+                (Some(b'@'), _) if !separate_argv0 => separate_argv0 = true,
+                (Some(b'-'), _) if !ignore_failure => ignore_failure = true,
+                (Some(b':'), _) if !no_env_expand => no_env_expand = true,
+                (Some(b'+'), ExecPrivileges::Default) => privileges = ExecPrivileges::Full,
+                (Some(b'!'), ExecPrivileges::Default) => privileges = ExecPrivileges::NoSetuid,
+                (Some(b'!'), ExecPrivileges::NoSetuid) => privileges = ExecPrivileges::AmbientMagic,
+                _ => break,
+            }
+            rest = &rest[1..];
+        }
+
+        let path = match extract_first_word(&mut rest, WHITESPACE, false)? {
+            Some(path) if !path.is_empty() => path,
+            _ => return Err(error("missing executable path")),
+        };
+        if !path.starts_with('/') && path.contains('/') {
+            return Err(error(
+                "executable path must be absolute or a plain file name",
+            ));
+        }
+        let mut argv = Vec::new();
+        if separate_argv0 {
+            match extract_first_word(&mut rest, WHITESPACE, false)? {
+                Some(argv0) => argv.push(argv0),
+                None => return Err(error("missing argv[0] after the `@` prefix")),
+            }
+        } else {
+            argv.push(path.clone());
+        }
+        argv.extend(split_words(rest, WHITESPACE, false)?);
+
+        Ok(Self {
+            path,
+            argv,
+            ignore_failure,
+            no_env_expand,
+            privileges,
+        })
+    }
+}
+
+impl ExecCommand {
+    /// Expands environment variables in every argument, including `argv[0]`, the way systemd's
+    /// `replace_env_argv()` does. `path` is left as is.
+    ///
+    /// An argument consisting of nothing but `$NAME` is replaced by the value of the variable split at
+    /// whitespace, resulting in zero or more arguments. `${NAME}` anywhere in an argument is replaced by the
+    /// exact value of the variable, see [`expand_env`]. Unset variables expand to nothing. Commands with
+    /// the `:` prefix are returned unchanged.
+    pub fn expand(&self, env: &HashMap<String, String>) -> Self {
+        if self.no_env_expand {
+            return self.clone();
+        }
+        let mut argv = Vec::with_capacity(self.argv.len());
+        for arg in self.argv.iter() {
+            match arg.strip_prefix('$').filter(|x| is_valid_env_name(x)) {
+                Some(name) => argv.extend(
+                    env.get(name)
+                        .iter()
+                        .flat_map(|x| x.split(|x| WHITESPACE.contains(x)))
+                        .filter(|x| !x.is_empty())
+                        .map(str::to_string),
+                ),
+                None => argv.push(expand_env(arg, env)),
+            }
+        }
+        Self {
+            argv,
+            ..self.clone()
+        }
+    }
+}
+
+/// Expands `${NAME}` with the value of the variable without splitting it, as well as `${NAME:-default}`
+/// (`default` if the variable is unset or empty) and `${NAME:+alternate}` (`alternate` if the variable is
+/// set and not empty). `$$` is replaced by a single `$`, any other `$` is left as is.
+pub fn expand_env(input: &str, env: &HashMap<String, String>) -> String {
+    let mut result = String::with_capacity(input.len());
+    let mut rest = input;
+    while let Some(index) = rest.find('$') {
+        result.push_str(&rest[..index]);
+        rest = &rest[index + 1..];
+        if let Some(after) = rest.strip_prefix('$') {
+            result.push('$');
+            rest = after;
+            continue;
+        }
+        let (expression, after) = match rest.strip_prefix('{').and_then(|x| x.split_once('}')) {
+            Some(split) => split,
+            None => {
+                result.push('$');
+                continue;
+            }
+        };
+        let value = |name: &str| env.get(name).map(String::as_str).unwrap_or_default();
+        if let Some((name, default)) = expression.split_once(":-") {
+            match value(name) {
+                "" => result.push_str(&expand_env(default, env)),
+                value => result.push_str(value),
+            }
+        } else if let Some((name, alternate)) = expression.split_once(":+") {
+            if !value(name).is_empty() {
+                result.push_str(&expand_env(alternate, env));
+            }
+        } else {
+            result.push_str(value(expression));
+        }
+        rest = after;
+    }
+    result.push_str(rest);
+    result
+}
+
+pub(crate) fn is_valid_env_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with(|x: char| x.is_ascii_digit())
+        && name.chars().all(|x| x.is_ascii_alphanumeric() || x == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        config::UnitEntry,
+        exec::{expand_env, ExecCommand, ExecPrivileges},
+    };
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_expand() {
+        let env = HashMap::from([
+            ("ONE".to_string(), "one".to_string()),
+            ("TWO".to_string(), "two  words".to_string()),
+            ("EMPTY".to_string(), "".to_string()),
+        ]);
+        let command = ExecCommand::parse_from_str(
+            r#"/bin/echo $TWO "${TWO}" x${ONE}y $$ONE $ONE$ONE $UNSET "$EMPTY""#,
+        )
+        .unwrap();
+        assert_eq!(command.path, "/bin/echo");
+        assert_eq!(
+            command.expand(&env).argv,
+            vec![
+                "/bin/echo",
+                "two",
+                "words",
+                "two  words",
+                "xoney",
+                "$ONE",
+                "$ONE$ONE"
+            ]
+        );
+
+        assert_eq!(
+            expand_env(
+                "${EMPTY:-none}-${ONE:+set}-${UNSET:+set}-${UNSET:-$$}",
+                &env
+            ),
+            "none-set--$"
+        );
+        assert_eq!(expand_env("${ONE", &env), "${ONE");
+
+        // `argv[0]` is expanded as well, but not the path
+        let command = ExecCommand::parse_from_str("@/bin/${ONE} ${ONE}-sh -c true").unwrap();
+        let expanded = command.expand(&env);
+        assert_eq!(expanded.path, "/bin/${ONE}");
+        assert_eq!(expanded.argv, vec!["one-sh", "-c", "true"]);
+        assert!(ExecCommand::parse_from_str("  ").is_err());
+        assert!(ExecCommand::parse_from_str("/bin/echo \"unbalanced").is_err());
+    }
+
+    #[test]
+    fn test_prefixes() {
+        let command =
+            ExecCommand::parse_from_str(r#"-@"/usr/bin/my daemon" daemon --flag"#).unwrap();
+        assert_eq!(command.path, "/usr/bin/my daemon");
+        assert_eq!(command.argv, vec!["daemon", "--flag"]);
+        assert!(command.ignore_failure);
+        assert!(!command.no_env_expand);
+        assert_eq!(command.privileges, ExecPrivileges::Default);
+
+        let command = ExecCommand::parse_from_str(":!!true $FOO").unwrap();
+        assert_eq!(command.argv, vec!["true", "$FOO"]);
+        assert!(command.no_env_expand);
+        assert_eq!(command.privileges, ExecPrivileges::AmbientMagic);
+        assert_eq!(command.expand(&HashMap::new()), command);
+        assert_eq!(
+            ExecCommand::parse_from_str("+/bin/true")
+                .unwrap()
+                .privileges,
+            ExecPrivileges::Full
+        );
+
+        assert!(ExecCommand::parse_from_str("--/bin/true").is_err());
+        assert!(ExecCommand::parse_from_str("+!/bin/true").is_err());
+        assert!(ExecCommand::parse_from_str("!!!/bin/true").is_err());
+        assert!(ExecCommand::parse_from_str("@/bin/true").is_err());
+        assert!(ExecCommand::parse_from_str("-").is_err());
+        assert!(ExecCommand::parse_from_str("bin/true").is_err());
+    }
+}
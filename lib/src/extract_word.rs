@@ -0,0 +1,210 @@
+use crate::{config::Result, error::Error};
+use std::borrow::Cow;
+
+pub const WHITESPACE: &str = " \t\n\r";
+
+/// Extracts the first word from `input` and advances it past the word, following the semantics of
+/// systemd's `extract_first_word()` with `EXTRACT_UNQUOTE | EXTRACT_CUNESCAPE`.
+///
+/// Words are delimited by any character in `separators`, single or double quotes group characters
+/// (including separators) into the same word and are removed, and backslashes escape the following
+/// character. In relaxed mode, unbalanced quotes and trailing backslashes are accepted as-is instead of
+/// being reported as errors.
+///
+/// Returns `Ok(None)` when there are no more words left.
+pub fn extract_first_word(
+    input: &mut &str,
+    separators: &str,
+    relax: bool,
+) -> Result<Option<String>> {
+    let trimmed = input.trim_start_matches(|x| separators.contains(x));
+    if trimmed.is_empty() {
+        *input = trimmed;
+        return Ok(None);
+    }
+
+    let mut result = String::new();
+    let mut quote = None;
+    let mut chars = trimmed.char_indices();
+    let mut end = trimmed.len();
+
+    while let Some((index, char)) = chars.next() {
+        match (char, quote) {
+            ('\\', _) => match chars.next() {
+                Some((_, escaped)) => result.push(unescape(escaped)),
+                None if relax => result.push('\\'),
+                None => {
+                    return Err(Error::WordExtractionError {
+                        input: input.to_string(),
+                    })
+                }
+            },
+            (x, Some(current)) if x == current => quote = None,
+            (_, Some(_)) => result.push(char),
+            ('\'' | '"', None) => quote = Some(char),
+            (x, None) if separators.contains(x) => {
+                end = index;
+                break;
+            }
+            (_, None) => result.push(char),
+        }
+    }
+
+    if quote.is_some() && !relax {
+        return Err(Error::WordExtractionError {
+            input: input.to_string(),
+        });
+    }
+
+    *input = trimmed[end..].trim_start_matches(|x| separators.contains(x));
+    Ok(Some(result))
+}
+
+/// Splits `input` into words with [`extract_first_word`], failing if any of the words is malformed.
+pub fn split_words(input: &str, separators: &str, relax: bool) -> Result<Vec<String>> {
+    let mut input = input;
+    let mut result = Vec::new();
+    while let Some(word) = extract_first_word(&mut input, separators, relax)? {
+        result.push(word);
+    }
+    Ok(result)
+}
+
+/// Extracts the next word of `value` starting at `offset` and advances `offset` past it.
+///
+/// The word borrows from `value` when it is borrowed itself and the word needed no unquoting or
+/// unescaping, so that borrowed multiple entries can be split without copying.
+pub fn extract_word_from<'a>(
+    value: &Cow<'a, str>,
+    offset: &mut usize,
+    separators: &str,
+    relax: bool,
+) -> Result<Option<Cow<'a, str>>> {
+    match value {
+        Cow::Borrowed(value) => {
+            let mut rest = &value[*offset..];
+            let start = rest.trim_start_matches(|x| separators.contains(x));
+            let word = extract_first_word(&mut rest, separators, relax)?;
+            *offset = value.len() - rest.len();
+            Ok(word.map(|word| match start.get(..word.len()) {
+                Some(raw) if raw == word => Cow::Borrowed(raw),
+                _ => Cow::Owned(word),
+            }))
+        }
+        Cow::Owned(value) => {
+            let mut rest = &value[*offset..];
+            let word = extract_first_word(&mut rest, separators, relax)?;
+            *offset = value.len() - rest.len();
+            Ok(word.map(Cow::Owned))
+        }
+    }
+}
+
+fn unescape(input: char) -> char {
+    match input {
+        'a' => '\u{07}',
+        'b' => '\u{08}',
+        'f' => '\u{0c}',
+        'n' => '\n',
+        'r' => '\r',
+        't' => '\t',
+        'v' => '\u{0b}',
+        'e' => '\u{1b}',
+        _ => input,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::extract_word::{extract_first_word, extract_word_from, split_words, WHITESPACE};
+    use std::borrow::Cow;
+
+    #[test]
+    fn test_plain() {
+        assert_eq!(
+            split_words("  foo bar\tbaz ", WHITESPACE, false).unwrap(),
+            vec!["foo", "bar", "baz"]
+        );
+        assert!(split_words("   ", WHITESPACE, false).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_quotes() {
+        assert_eq!(
+            split_words(r#""FOO=a b" BAR=c 'x y'z"#, WHITESPACE, false).unwrap(),
+            vec!["FOO=a b", "BAR=c", "x yz"]
+        );
+        assert_eq!(
+            split_words(r#"FOO="a b" "it's""#, WHITESPACE, false).unwrap(),
+            vec!["FOO=a b", "it's"]
+        );
+        assert_eq!(
+            split_words(r#""" x"#, WHITESPACE, false).unwrap(),
+            vec!["", "x"]
+        );
+    }
+
+    #[test]
+    fn test_escapes() {
+        assert_eq!(
+            split_words(r#"/path/with\ space "a\"b" c\td"#, WHITESPACE, false).unwrap(),
+            vec!["/path/with space", "a\"b", "c\td"]
+        );
+    }
+
+    #[test]
+    fn test_unbalanced() {
+        assert!(split_words(r#"foo "bar"#, WHITESPACE, false).is_err());
+        assert!(split_words(r#"foo bar\"#, WHITESPACE, false).is_err());
+        assert_eq!(
+            split_words(r#"foo "bar baz"#, WHITESPACE, true).unwrap(),
+            vec!["foo", "bar baz"]
+        );
+        assert_eq!(
+            split_words(r#"foo bar\"#, WHITESPACE, true).unwrap(),
+            vec!["foo", "bar\\"]
+        );
+    }
+
+    #[test]
+    fn test_separator() {
+        assert_eq!(
+            split_words("GNOME;GTK;Utility;", ";", false).unwrap(),
+            vec!["GNOME", "GTK", "Utility"]
+        );
+        assert_eq!(
+            split_words(r#"a\;b;c d"#, ";", false).unwrap(),
+            vec!["a;b", "c d"]
+        );
+    }
+
+    #[test]
+    fn test_partial() {
+        let mut input = r#"first "second"#;
+        assert_eq!(
+            extract_first_word(&mut input, WHITESPACE, false).unwrap(),
+            Some("first".to_string())
+        );
+        assert!(extract_first_word(&mut input, WHITESPACE, false).is_err());
+    }
+
+    #[test]
+    fn test_extract_word_from() {
+        let value = Cow::Borrowed(" plain \"quoted word\" esc\\-aped");
+        let mut offset = 0;
+        let mut words = Vec::new();
+        while let Some(word) = extract_word_from(&value, &mut offset, WHITESPACE, false).unwrap() {
+            words.push(word);
+        }
+        assert_eq!(words, vec!["plain", "quoted word", "esc-aped"]);
+        assert!(matches!(words[0], Cow::Borrowed("plain")));
+        assert!(matches!(words[1], Cow::Owned(_)));
+        assert!(matches!(words[2], Cow::Owned(_)));
+
+        let value = Cow::Owned("first second".to_string());
+        let mut offset = 0;
+        let word = extract_word_from(&value, &mut offset, WHITESPACE, false).unwrap();
+        assert!(matches!(word, Some(Cow::Owned(x)) if x == "first"));
+        assert_eq!(&value[offset..], "second");
+    }
+}
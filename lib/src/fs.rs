@@ -0,0 +1,559 @@
+use std::{
+    collections::BTreeMap,
+    fmt, fs, io,
+    path::{Component, Path, PathBuf},
+    rc::Rc,
+    time::{Duration, SystemTime},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileType {
+    File,
+    Directory,
+    Symlink,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileMetadata {
+    pub file_type: FileType,
+    pub modified: SystemTime,
+    pub len: u64,
+}
+
+/// The filesystem units are loaded from.
+///
+/// [`StdFs`] reads from disk, while [`MemoryFs`] serves files from memory, e.g. for test fixtures or
+/// files extracted from archives and image layers.
+pub trait UnitFs {
+    fn read_to_string(&self, path: &Path) -> io::Result<String>;
+
+    /// Returns the paths of all entries in the directory, in no particular order.
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>>;
+
+    /// Queries metadata, following symlinks.
+    fn metadata(&self, path: &Path) -> io::Result<FileMetadata>;
+
+    /// Queries metadata without following symlinks.
+    fn symlink_metadata(&self, path: &Path) -> io::Result<FileMetadata>;
+
+    /// Returns the target of a symlink.
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf>;
+
+    fn is_dir(&self, path: &Path) -> bool {
+        self.metadata(path)
+            .is_ok_and(|x| x.file_type == FileType::Directory)
+    }
+}
+
+/// Reads units from disk with `std::fs`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StdFs;
+
+impl StdFs {
+    fn convert(metadata: fs::Metadata) -> FileMetadata {
+        let file_type = if metadata.is_symlink() {
+            FileType::Symlink
+        } else if metadata.is_dir() {
+            FileType::Directory
+        } else {
+            FileType::File
+        };
+        FileMetadata {
+            file_type,
+            modified: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+            len: metadata.len(),
+        }
+    }
+}
+
+impl UnitFs for StdFs {
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        fs::read_to_string(path)
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        fs::read_dir(path)?.map(|x| x.map(|x| x.path())).collect()
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<FileMetadata> {
+        fs::metadata(path).map(Self::convert)
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> io::Result<FileMetadata> {
+        fs::symlink_metadata(path).map(Self::convert)
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        fs::read_link(path)
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    File(String),
+    Directory,
+    Symlink(PathBuf),
+}
+
+/// An in-memory filesystem.
+///
+/// Parent directories are created implicitly, and modification times increase with every change so
+/// that [`UnitCache`](crate::prelude::UnitCache) notices them.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryFs {
+    nodes: BTreeMap<PathBuf, (Node, SystemTime)>,
+    generation: u64,
+}
+
+// guards against symlink loops
+const MAX_SYMLINKS: usize = 32;
+
+impl MemoryFs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_file<P: AsRef<Path>, S: Into<String>>(&mut self, path: P, content: S) -> &mut Self {
+        self.insert(path.as_ref(), Node::File(content.into()));
+        self
+    }
+
+    pub fn add_dir<P: AsRef<Path>>(&mut self, path: P) -> &mut Self {
+        self.insert(path.as_ref(), Node::Directory);
+        self
+    }
+
+    /// Adds a symlink pointing at `target`, which is resolved relative to the symlink's directory if
+    /// it is relative.
+    pub fn add_symlink<P: AsRef<Path>, T: AsRef<Path>>(&mut self, path: P, target: T) -> &mut Self {
+        self.insert(path.as_ref(), Node::Symlink(target.as_ref().to_owned()));
+        self
+    }
+
+    pub fn remove<P: AsRef<Path>>(&mut self, path: P) -> &mut Self {
+        let path = path.as_ref();
+        self.nodes.retain(|x, _| !x.starts_with(path));
+        if let Some(parent) = path.parent() {
+            self.touch(parent);
+        }
+        self
+    }
+
+    fn insert(&mut self, path: &Path, node: Node) {
+        for ancestor in path.ancestors().skip(1) {
+            if ancestor.as_os_str().is_empty() {
+                break;
+            }
+            self.nodes
+                .entry(ancestor.to_owned())
+                .or_insert((Node::Directory, SystemTime::UNIX_EPOCH));
+        }
+        let modified = self.tick();
+        self.nodes.insert(path.to_owned(), (node, modified));
+        if let Some(parent) = path.parent() {
+            self.touch(parent);
+        }
+    }
+
+    // adding or removing entries changes the modification time of the directory, just like on disk
+    fn touch(&mut self, path: &Path) {
+        let modified = self.tick();
+        if let Some((_, time)) = self.nodes.get_mut(path) {
+            *time = modified;
+        }
+    }
+
+    fn tick(&mut self) -> SystemTime {
+        self.generation += 1;
+        SystemTime::UNIX_EPOCH + Duration::from_nanos(self.generation)
+    }
+
+    fn get(&self, path: &Path) -> io::Result<&(Node, SystemTime)> {
+        self.nodes
+            .get(path)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, path.to_string_lossy()))
+    }
+
+    // follows symlinks in every component of the path
+    fn resolve(&self, path: &Path) -> io::Result<(PathBuf, &(Node, SystemTime))> {
+        let mut path = normalize(path);
+        let mut followed = 0;
+        'restart: loop {
+            let mut prefix = PathBuf::new();
+            for component in path.components() {
+                prefix.push(component);
+                if let Some((Node::Symlink(target), _)) = self.nodes.get(&prefix) {
+                    followed += 1;
+                    if followed > MAX_SYMLINKS {
+                        return Err(io::Error::other(format!(
+                            "Too many levels of symbolic links: {}",
+                            path.to_string_lossy()
+                        )));
+                    }
+                    let rest = path.strip_prefix(&prefix).unwrap_or(Path::new(""));
+                    let base = prefix.parent().unwrap_or(Path::new(""));
+                    path = normalize(&base.join(target).join(rest));
+                    continue 'restart;
+                }
+            }
+            let node = self.get(&path)?;
+            return Ok((path, node));
+        }
+    }
+
+    // follows symlinks in every component but the last
+    fn get_unresolved(&self, path: &Path) -> io::Result<&(Node, SystemTime)> {
+        let path = normalize(path);
+        match (path.parent(), path.file_name()) {
+            (Some(parent), Some(name)) => {
+                let parent = self.resolve(parent)?.0;
+                self.get(&parent.join(name))
+            }
+            _ => self.get(&path),
+        }
+    }
+
+    fn convert(node: &(Node, SystemTime)) -> FileMetadata {
+        let (file_type, len) = match &node.0 {
+            Node::File(content) => (FileType::File, content.len() as u64),
+            Node::Directory => (FileType::Directory, 0),
+            Node::Symlink(target) => (FileType::Symlink, target.as_os_str().len() as u64),
+        };
+        FileMetadata {
+            file_type,
+            modified: node.1,
+            len,
+        }
+    }
+}
+
+impl UnitFs for MemoryFs {
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        match self.resolve(path)?.1 {
+            (Node::File(content), _) => Ok(content.to_owned()),
+            _ => Err(io::Error::other(format!(
+                "{} is not a file",
+                path.to_string_lossy()
+            ))),
+        }
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        let (path, node) = self.resolve(path)?;
+        if !matches!(node.0, Node::Directory) {
+            return Err(io::Error::other(format!(
+                "{} is not a directory",
+                path.to_string_lossy()
+            )));
+        }
+        Ok(self
+            .nodes
+            .range(path.clone()..)
+            .map(|x| x.0)
+            .skip_while(|x| **x == path)
+            .take_while(|x| x.starts_with(&path))
+            .filter(|x| x.parent() == Some(path.as_path()))
+            .cloned()
+            .collect())
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<FileMetadata> {
+        self.resolve(path).map(|x| Self::convert(x.1))
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> io::Result<FileMetadata> {
+        self.get_unresolved(path).map(Self::convert)
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        match self.get_unresolved(path)? {
+            (Node::Symlink(target), _) => Ok(target.to_owned()),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is not a symlink", path.to_string_lossy()),
+            )),
+        }
+    }
+}
+
+/// Confines another [`UnitFs`] to a root directory, like `systemctl --root=` does.
+///
+/// Paths are interpreted relative to the root, and symlinks are resolved within it, including those
+/// with absolute targets, so nothing outside of the root is ever accessed. Paths returned and reported
+/// by `%y` and `%Y` stay relative to the root, i.e. they are the paths the units would have if the root
+/// was mounted at `/`.
+pub struct RootedFs {
+    root: PathBuf,
+    inner: Rc<dyn UnitFs>,
+}
+
+impl RootedFs {
+    /// Confines the disk to `root`.
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        Self::with_fs(root, Rc::new(StdFs))
+    }
+
+    pub fn with_fs<P: AsRef<Path>>(root: P, inner: Rc<dyn UnitFs>) -> Self {
+        Self {
+            root: root.as_ref().to_owned(),
+            inner,
+        }
+    }
+
+    fn host_path(&self, path: &Path) -> PathBuf {
+        let mut result = self.root.clone();
+        for component in path.components() {
+            if let Component::Normal(name) = component {
+                result.push(name);
+            }
+        }
+        result
+    }
+
+    // resolves symlinks in every component of the path if `last` is set, or in every component but the
+    // last otherwise, and returns the resulting path relative to the root
+    fn resolve(&self, path: &Path, last: bool) -> io::Result<PathBuf> {
+        let mut pending: Vec<PathBuf> = path
+            .components()
+            .rev()
+            .map(|x| PathBuf::from(x.as_os_str()))
+            .collect();
+        let mut result = PathBuf::from("/");
+        let mut followed = 0;
+        while let Some(component) = pending.pop() {
+            match component.components().next() {
+                Some(Component::Normal(name)) => {
+                    let candidate = result.join(name);
+                    if pending.is_empty() && !last {
+                        return Ok(candidate);
+                    }
+                    let host = self.host_path(&candidate);
+                    match self.inner.symlink_metadata(&host) {
+                        Ok(meta) if meta.file_type == FileType::Symlink => {
+                            followed += 1;
+                            if followed > MAX_SYMLINKS {
+                                return Err(io::Error::other(format!(
+                                    "Too many levels of symbolic links: {}",
+                                    path.to_string_lossy()
+                                )));
+                            }
+                            let target = self.inner.read_link(&host)?;
+                            // absolute targets start over at the root, relative ones at the directory
+                            // of the symlink
+                            if target.is_absolute() {
+                                result = PathBuf::from("/");
+                            }
+                            pending.extend(
+                                target
+                                    .components()
+                                    .rev()
+                                    .map(|x| PathBuf::from(x.as_os_str())),
+                            );
+                        }
+                        _ => result = candidate,
+                    }
+                }
+                // `..` never leaves the root, just like in a chroot
+                Some(Component::ParentDir) => {
+                    result.pop();
+                }
+                _ => {}
+            }
+        }
+        Ok(result)
+    }
+}
+
+impl fmt::Debug for RootedFs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RootedFs")
+            .field("root", &self.root)
+            .finish_non_exhaustive()
+    }
+}
+
+impl UnitFs for RootedFs {
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        let path = self.resolve(path, true)?;
+        self.inner.read_to_string(&self.host_path(&path))
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        let resolved = self.resolve(path, true)?;
+        // entries are reported under the path that was asked for, not the resolved one
+        Ok(self
+            .inner
+            .read_dir(&self.host_path(&resolved))?
+            .into_iter()
+            .filter_map(|x| x.file_name().map(|name| path.join(name)))
+            .collect())
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<FileMetadata> {
+        let path = self.resolve(path, true)?;
+        self.inner.symlink_metadata(&self.host_path(&path))
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> io::Result<FileMetadata> {
+        let path = self.resolve(path, false)?;
+        self.inner.symlink_metadata(&self.host_path(&path))
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        let path = self.resolve(path, false)?;
+        self.inner.read_link(&self.host_path(&path))
+    }
+}
+
+// resolves `.` and `..` lexically, symlinks are taken care of by `MemoryFs::resolve`
+pub(crate) fn normalize(path: &Path) -> PathBuf {
+    let mut result = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                result.pop();
+            }
+            component => result.push(component),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        config::UnitFiles,
+        fs::{FileType, MemoryFs, RootedFs, UnitFs},
+    };
+    use std::{
+        path::{Path, PathBuf},
+        rc::Rc,
+    };
+
+    #[test]
+    fn test_memory_fs() {
+        let mut fs = MemoryFs::new();
+        fs.add_file("/etc/systemd/system/foo.service", "[Unit]\n")
+            .add_file("/etc/systemd/system/foo.service.d/a.conf", "[Unit]\n")
+            .add_symlink(
+                "/etc/systemd/system/multi-user.target.wants/foo.service",
+                "../foo.service",
+            );
+
+        assert_eq!(
+            fs.read_to_string(Path::new("/etc/systemd/system/foo.service"))
+                .unwrap(),
+            "[Unit]\n"
+        );
+        assert!(fs.is_dir(Path::new("/etc/systemd")));
+        assert!(fs.read_to_string(Path::new("/etc/missing")).is_err());
+
+        let mut entries = fs.read_dir(Path::new("/etc/systemd/system")).unwrap();
+        entries.sort();
+        assert_eq!(
+            entries,
+            vec![
+                PathBuf::from("/etc/systemd/system/foo.service"),
+                PathBuf::from("/etc/systemd/system/foo.service.d"),
+                PathBuf::from("/etc/systemd/system/multi-user.target.wants"),
+            ]
+        );
+
+        let link = Path::new("/etc/systemd/system/multi-user.target.wants/foo.service");
+        assert_eq!(
+            fs.symlink_metadata(link).unwrap().file_type,
+            FileType::Symlink
+        );
+        assert_eq!(fs.metadata(link).unwrap().file_type, FileType::File);
+        assert_eq!(fs.read_to_string(link).unwrap(), "[Unit]\n");
+    }
+
+    #[test]
+    fn test_memory_fs_changes() {
+        let mut fs = MemoryFs::new();
+        fs.add_file("/foo.service.d/a.conf", "");
+        let dir = fs.metadata(Path::new("/foo.service.d")).unwrap();
+        fs.add_file("/foo.service.d/b.conf", "");
+        assert_ne!(dir, fs.metadata(Path::new("/foo.service.d")).unwrap());
+
+        fs.remove("/foo.service.d");
+        assert!(!fs.is_dir(Path::new("/foo.service.d")));
+        fs.add_symlink("/etc/systemd/system", "/usr/lib/systemd/system")
+            .add_file("/usr/lib/systemd/system/foo.service", "[Unit]\n");
+        assert!(fs
+            .read_to_string(Path::new("/etc/systemd/system/foo.service"))
+            .is_ok());
+        fs.add_symlink("/loop", "/loop");
+        assert!(fs.metadata(Path::new("/loop")).is_err());
+    }
+
+    #[test]
+    fn test_rooted_fs() {
+        let mut image = MemoryFs::new();
+        image
+            .add_file("/image/usr/lib/systemd/system/foo.service", "[Unit]\n")
+            .add_file("/image/usr/lib/systemd/system/bar.conf", "[Unit]\n")
+            .add_file("/usr/lib/systemd/system/foo.service", "[Host]\n")
+            .add_symlink(
+                "/image/etc/systemd/system/foo.service",
+                "/usr/lib/systemd/system/foo.service",
+            )
+            .add_symlink(
+                "/image/etc/systemd/system/foo.service.d",
+                "../../../usr/lib/systemd/system",
+            )
+            .add_symlink(
+                "/image/etc/systemd/system/escape.service",
+                "../../../../../../usr/lib/systemd/system/foo.service",
+            )
+            .add_symlink(
+                "/image/etc/systemd/system/foo.service.wants/bar.service",
+                "/usr/lib/systemd/system/bar.service",
+            );
+        let fs = RootedFs::with_fs("/image", Rc::new(image));
+
+        let foo = Path::new("/etc/systemd/system/foo.service");
+        assert_eq!(fs.read_to_string(foo).unwrap(), "[Unit]\n");
+        assert_eq!(
+            fs.symlink_metadata(foo).unwrap().file_type,
+            FileType::Symlink
+        );
+        assert_eq!(
+            fs.read_to_string(Path::new("/etc/systemd/system/escape.service"))
+                .unwrap(),
+            "[Unit]\n"
+        );
+        assert!(fs
+            .read_to_string(Path::new("/../usr/lib/systemd/system/foo.service"))
+            .is_ok());
+
+        let wants = fs
+            .read_dir(Path::new("/etc/systemd/system/foo.service.wants"))
+            .unwrap();
+        assert_eq!(
+            wants,
+            vec![PathBuf::from(
+                "/etc/systemd/system/foo.service.wants/bar.service"
+            )]
+        );
+        assert_eq!(
+            fs.symlink_metadata(&wants[0]).unwrap().file_type,
+            FileType::Symlink
+        );
+        assert!(fs.metadata(&wants[0]).is_err());
+
+        // paths stay relative to the root
+        let files =
+            UnitFiles::read_named_from(Rc::new(fs), vec!["/etc/systemd/system"], "foo", "service")
+                .unwrap();
+        assert_eq!(files.fragment.0, foo);
+        assert_eq!(
+            files.dropins,
+            vec![(
+                PathBuf::from("/etc/systemd/system/foo.service.d/bar.conf"),
+                "[Unit]\n".to_string()
+            )]
+        );
+    }
+}
@@ -1,3 +1,5 @@
-pub use crate::config::{Result, UnitConfig, UnitEntry, UnitSection};
+pub use crate::config::{Result, SectionKeys, UnitConfig, UnitEntry, UnitEntryRef, UnitSection};
 pub use crate::error::Error;
-pub use crate::parser::{SectionParser, UnitParser};
+pub use crate::extract_word::{extract_first_word, extract_word_from, split_words, WHITESPACE};
+pub use crate::lexer::{join_continuation, Lexer, Token};
+pub use crate::parser::{SectionParser, SubdirParser, UnitParser};
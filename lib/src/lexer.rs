@@ -0,0 +1,221 @@
+use crate::{config::Result, error::Error};
+
+pub enum Token<'a> {
+    Section(&'a str),
+    // a legacy `.include` line, `line` spans the whole directive
+    Include {
+        path: &'a str,
+        line: &'a str,
+    },
+    // `value` spans all continuation lines if `continued` is set, see `join_continuation`
+    Entry {
+        key: &'a str,
+        value: &'a str,
+        continued: bool,
+    },
+}
+
+/// A line-based lexer for unit files, following the rules of systemd's `config_parse()`.
+#[derive(Clone)]
+pub struct Lexer<'a> {
+    input: &'a str,
+    position: usize,
+    line: usize,
+}
+
+impl<'a> Lexer<'a> {
+    pub fn new(input: &'a str) -> Self {
+        Self {
+            input,
+            position: 0,
+            line: 0,
+        }
+    }
+
+    pub(crate) fn line(&self) -> usize {
+        self.line
+    }
+
+    // returns the next line with surrounding whitespace stripped
+    fn next_line(&mut self) -> Option<&'a str> {
+        if self.position >= self.input.len() {
+            return None;
+        }
+        let rest = &self.input[self.position..];
+        let (line, advance) = match rest.find('\n') {
+            Some(end) => (&rest[..end], end + 1),
+            None => (rest, rest.len()),
+        };
+        self.position += advance;
+        self.line += 1;
+        Some(trim(line))
+    }
+
+    pub(crate) fn offset(&self, slice: &str) -> usize {
+        slice.as_ptr() as usize - self.input.as_ptr() as usize
+    }
+
+    fn error(&self, reason: &'static str) -> Error {
+        Error::ParsingError {
+            line: self.line,
+            reason,
+        }
+    }
+}
+
+impl<'a> Iterator for Lexer<'a> {
+    type Item = Result<Token<'a>>;
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let line = self.next_line()?;
+            match line.as_bytes().first() {
+                None | Some(b'#' | b';') => continue,
+                Some(b'[') => {
+                    return Some(match line.strip_suffix(']') {
+                        Some(name) if name.len() > 1 => Ok(Token::Section(&name[1..])),
+                        _ => Err(self.error("invalid section header")),
+                    });
+                }
+                Some(b'.') if is_include(line) => {
+                    return Some(Ok(Token::Include {
+                        path: trim(&line[".include".len()..]),
+                        line,
+                    }));
+                }
+                Some(_) => {
+                    let (key, value) = match line.split_once('=') {
+                        Some(pair) => pair,
+                        None => return Some(Err(self.error("expecting an assignment"))),
+                    };
+                    let key = trim(key);
+                    if key.is_empty() {
+                        return Some(Err(self.error("missing key before `=`")));
+                    }
+                    let value = trim(value);
+                    if !ends_with_escape(value) {
+                        return Some(Ok(Token::Entry {
+                            key,
+                            value,
+                            continued: false,
+                        }));
+                    }
+
+                    // comment lines within continuations are skipped, but empty lines end them
+                    let start = self.offset(value);
+                    let mut end = start + value.len();
+                    while let Some(next) = self.next_line() {
+                        end = self.offset(next) + next.len();
+                        if !(is_comment(next) || ends_with_escape(next)) {
+                            break;
+                        }
+                    }
+                    return Some(Ok(Token::Entry {
+                        key,
+                        value: &self.input[start..end],
+                        continued: true,
+                    }));
+                }
+            }
+        }
+    }
+}
+
+/// Joins the lines of a continued value, replacing each trailing backslash with a space.
+pub fn join_continuation(input: &str) -> String {
+    let mut result = String::with_capacity(input.len());
+    for (index, line) in input.split('\n').enumerate() {
+        let line = trim(line);
+        if index > 0 && is_comment(line) {
+            continue;
+        }
+        if ends_with_escape(line) {
+            result.push_str(&line[..line.len() - 1]);
+            result.push(' ');
+        } else {
+            result.push_str(line);
+        }
+    }
+    result
+}
+
+fn trim(input: &str) -> &str {
+    input.trim_matches(|x| matches!(x, ' ' | '\t' | '\r'))
+}
+
+fn is_include(input: &str) -> bool {
+    input
+        .strip_prefix(".include")
+        .is_some_and(|x| x.starts_with([' ', '\t']))
+}
+
+fn is_comment(input: &str) -> bool {
+    input.starts_with('#') || input.starts_with(';')
+}
+
+// a trailing backslash only continues the line if it is not escaped itself
+fn ends_with_escape(input: &str) -> bool {
+    input.bytes().rev().take_while(|x| *x == b'\\').count() % 2 == 1
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lexer::{join_continuation, Lexer, Token};
+
+    fn tokens(input: &str) -> Vec<String> {
+        Lexer::new(input)
+            .map(|x| match x.unwrap() {
+                Token::Section(name) => format!("[{}]", name),
+                Token::Include { path, .. } => format!(".include {}", path),
+                Token::Entry {
+                    key,
+                    value,
+                    continued: false,
+                } => format!("{}={}", key, value),
+                Token::Entry { key, value, .. } => format!("{}={}", key, join_continuation(value)),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_basic() {
+        let input = "\n# comment\n[Unit]\nDescription = Some unit \r\n; comment\n .include  /a.service\n\n[Desktop Entry]\nName[de]=Foo\nEmpty=\n";
+        assert_eq!(
+            tokens(input),
+            vec![
+                "[Unit]",
+                "Description=Some unit",
+                ".include /a.service",
+                "[Desktop Entry]",
+                "Name[de]=Foo",
+                "Empty="
+            ]
+        );
+    }
+
+    #[test]
+    fn test_continuation() {
+        let input = "[Service]\nExecStart=/bin/echo \\\n  first \\\n# comment \\\n  second\nNext=\\\\\nLast=a\\\n";
+        assert_eq!(
+            tokens(input),
+            vec![
+                "[Service]",
+                "ExecStart=/bin/echo  first  second",
+                "Next=\\\\",
+                "Last=a ",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut lexer = Lexer::new("[Unit]\nDescription=\n\nNetwork Manager\n");
+        assert!(lexer.next().unwrap().is_ok());
+        assert!(lexer.next().unwrap().is_ok());
+        assert!(lexer.next().unwrap().is_err());
+        assert_eq!(lexer.line(), 4);
+
+        assert!(Lexer::new("[Unit\n").next().unwrap().is_err());
+        assert!(Lexer::new("[]\n").next().unwrap().is_err());
+        assert!(Lexer::new("=value\n").next().unwrap().is_err());
+    }
+}
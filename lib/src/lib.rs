@@ -1,9 +1,21 @@
+// lets the derive macros be used within this crate
+extern crate self as unit_parser;
+
+mod cache;
 mod config;
+mod environment;
 mod error;
 mod escape;
+mod exec;
+mod extract_word;
+mod fs;
+mod lexer;
 mod parser;
+mod search_paths;
+mod size;
 mod specifiers;
 mod template;
+mod timer;
 
 // pub mod calender_events;
 mod datetime;
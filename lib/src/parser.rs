@@ -1,42 +1,61 @@
 use std::{
-    fs::read_dir,
+    borrow::Cow,
+    cell::RefCell,
     path::{Path, PathBuf},
     rc::Rc,
 };
 
-use crate::{config::Result, error::*, specifiers::resolve};
-use pest::{iterators::Pairs, Parser};
-use pest_derive::Parser;
-use snafu::ResultExt;
-
-#[derive(Parser, Debug)]
-#[grammar = "unit.pest"]
-pub struct UnitFileParser;
+use crate::{
+    config::Result,
+    error::*,
+    fs::{FileType, UnitFs},
+    lexer::{join_continuation, Lexer, Token},
+    specifiers::resolve,
+    template::{is_valid_unit_name, unit_type, UnitType},
+};
 
 pub struct UnitParser<'a> {
-    paths: Rc<Vec<PathBuf>>,
-    filename: &'a str,
+    filename: Rc<str>,
     path: &'a Path,
-    inner: Pairs<'a, Rule>,
+    state: Rc<RefCell<LexerState<'a>>>,
     root: bool,
 }
 
+// the lexer is shared between a `UnitParser` and its `SectionParser`s, so that every line is lexed once:
+// entries consumed by a section are not lexed again when moving on to the next section
+struct LexerState<'a> {
+    lexer: Lexer<'a>,
+    // the index of the section being lexed, sections before it no longer yield entries
+    section: usize,
+    // the header that ended the previous section, if a `SectionParser` reached it
+    next: Option<&'a str>,
+}
+
 impl<'a> UnitParser<'a> {
-    pub(crate) fn new(
-        input: &'a str,
-        paths: Rc<Vec<PathBuf>>,
-        root: bool,
-        filename: &'a str,
-        path: &'a Path,
-    ) -> Result<Self> {
-        let mut parse =
-            UnitFileParser::parse(Rule::unit_file, input.as_ref()).context(ParsingSnafu {})?;
-        // should never fail since rule unit_file restricts SOI and EOI
-        let sections = parse.next().unwrap().into_inner();
+    pub fn new(input: &'a str, root: bool, filename: &'a str, path: &'a Path) -> Result<Self> {
+        let mut lexer = Lexer::new(input);
+
+        // the rest of the file is lexed lazily, but it should at least start with a section
+        let next = match lexer.next() {
+            None => return Err(Error::NoSectionError),
+            Some(Err(err)) => return Err(err),
+            Some(Ok(Token::Entry { .. })) => {
+                return Err(Error::ParsingError {
+                    line: lexer.line(),
+                    reason: "assignment outside of a section",
+                })
+            }
+            Some(Ok(Token::Include { .. })) => return Err(include_error(lexer.line())),
+            Some(Ok(Token::Section(name))) => name,
+        };
+
         Ok(Self {
-            inner: sections,
-            paths,
-            filename,
+            state: Rc::new(RefCell::new(LexerState {
+                lexer,
+                section: 0,
+                next: Some(next),
+            })),
+            filename: filename.into(),
             path,
             root,
         })
@@ -46,91 +65,73 @@ impl<'a> UnitParser<'a> {
 impl<'a> Iterator for UnitParser<'a> {
     type Item = Result<SectionParser<'a>>;
     fn next(&mut self) -> Option<Self::Item> {
-        let item = self.inner.next().unwrap();
-        if item.as_rule() == Rule::EOI {
-            return None;
-        }
-
-        if item.as_rule() != Rule::section {
-            return Some(Err(Error::SectionError {
-                actual: item.as_rule(),
-            }));
-        }
-
-        let mut inner = item.into_inner();
-
-        let first_item = inner.next().unwrap();
-
-        // probably also not needed as it would have already violated grammar test, but if we make the grammar
-        // less restrictive, then error messages would be more detailed
-        if first_item.as_rule() != Rule::section_header {
-            return Some(Err(Error::SectionNameError {
-                actual: first_item.as_rule(),
-            }));
-        }
-
-        let section_name = first_item.as_str();
-
-        let paths = Rc::clone(&self.paths);
-
+        let mut state = self.state.borrow_mut();
+        state.section += 1;
+        let name = match state.next.take() {
+            Some(name) => name,
+            None => loop {
+                match state.lexer.next()? {
+                    Ok(Token::Section(name)) => break name,
+                    // entries the previous section did not consume
+                    Ok(Token::Entry { .. }) => continue,
+                    Ok(Token::Include { .. }) => {
+                        return Some(Err(include_error(state.lexer.line())))
+                    }
+                    Err(err) => return Some(Err(err)),
+                }
+            },
+        };
         Some(Ok(SectionParser {
-            paths,
-            name: section_name,
-            inner,
+            name,
+            state: Rc::clone(&self.state),
+            section: state.section,
             path: self.path,
-            filename: self.filename.into(),
+            filename: Rc::clone(&self.filename),
             root: self.root,
         }))
     }
 }
 
+// `.include`s are only inlined by `UnitFiles` for units that opt in with `#[unit(include)]`
+fn include_error(line: usize) -> Error {
+    Error::ParsingError {
+        line,
+        reason: "`.include` is not enabled for this unit",
+    }
+}
+
 pub struct SectionParser<'a> {
-    paths: Rc<Vec<PathBuf>>,
     pub name: &'a str,
-    inner: Pairs<'a, Rule>,
+    state: Rc<RefCell<LexerState<'a>>>,
+    section: usize,
     filename: Rc<str>,
     path: &'a Path,
     root: bool,
 }
 
-impl<'a> Iterator for SectionParser<'a> {
-    type Item = Result<(&'a str, String)>;
-    fn next(&mut self) -> Option<Self::Item> {
-        let entry = self.inner.next();
-        if let Some(entry) = entry {
-            if entry.as_rule() != Rule::entry {
-                return Some(Err(Error::EntryError {
-                    actual: entry.as_rule(),
-                }));
-            }
-
-            let mut entry_inner = entry.into_inner();
+impl<'a> SectionParser<'a> {
+    // borrow from the input when neither specifiers nor line continuations rewrote the value
+    fn resolve_value(&self, value: &'a str, continued: bool) -> Cow<'a, str> {
+        let value = match continued {
+            true => Cow::Owned(join_continuation(value)),
+            false => Cow::Borrowed(value),
+        };
+        if !value.contains('%') {
+            return value;
+        }
 
-            // should not fail as the contents of an entry is restricted
-            let key = entry_inner.next().unwrap();
-            if key.as_rule() != Rule::key {
-                return Some(Err(Error::EntryKeyError {
-                    actual: key.as_rule(),
-                }));
-            }
-            let key = key.as_str();
-
-            // should not fail as the contents of an entry is restricted
-            let values = entry_inner.next().unwrap();
-            if values.as_rule() != Rule::value {
-                return Some(Err(Error::EntryValueError {
-                    actual: values.as_rule(),
-                }));
+        let mut result = String::with_capacity(value.len());
+        let mut chars = value.chars();
+        while let Some(char) = chars.next() {
+            if char != '%' {
+                result.push(char);
+                continue;
             }
-
-            let mut value = String::new();
-            for item in values.into_inner() {
-                if item.as_rule() == Rule::value_block {
-                    value.push_str(item.as_str());
-                } else {
+            match chars.next() {
+                Some(specifier) => {
                     resolve(
-                        &mut value,
-                        item.as_str().chars().nth(0).unwrap(),
+                        &mut result,
+                        specifier,
                         self.root,
                         self.filename.as_ref(),
                         self.path,
@@ -138,44 +139,112 @@ impl<'a> Iterator for SectionParser<'a> {
                     .map_err(|x| log::warn!("Error occured while resolving specifier: {}", x))
                     .ok();
                 }
+                None => log::warn!("Missing specifier after % in {}.", value),
             }
+        }
+        Cow::Owned(result)
+    }
+}
 
-            return Some(Ok((key, value)));
-        } else {
+impl<'a> Iterator for SectionParser<'a> {
+    type Item = Result<(&'a str, Cow<'a, str>)>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut state = self.state.borrow_mut();
+        // the unit parser moved on, or this section already ended
+        if state.section != self.section || state.next.is_some() {
             return None;
         }
+        match state.lexer.next()? {
+            Ok(Token::Entry {
+                key,
+                value,
+                continued,
+            }) => Some(Ok((key, self.resolve_value(value, continued)))),
+            Ok(Token::Section(name)) => {
+                state.next = Some(name);
+                None
+            }
+            Ok(Token::Include { .. }) => Some(Err(include_error(state.lexer.line()))),
+            Err(err) => Some(Err(err)),
+        }
     }
 }
 
+/// Collects the units listed in subdirs such as `.wants` and `.requires`.
 pub struct SubdirParser {
+    fs: Rc<dyn UnitFs>,
     paths: Rc<Vec<PathBuf>>,
     filename: Rc<str>,
 }
 
-impl<'a> SectionParser<'a> {
-    pub fn __subdir_parser(&'a self) -> SubdirParser {
-        let paths = Rc::clone(&self.paths);
-        let filename = Rc::clone(&self.filename);
-
-        SubdirParser { paths, filename }
+impl SubdirParser {
+    pub(crate) fn new(fs: Rc<dyn UnitFs>, paths: Rc<Vec<PathBuf>>, filename: &str) -> Self {
+        Self {
+            fs,
+            paths,
+            filename: filename.into(),
+        }
     }
-}
 
-impl SubdirParser {
+    /// Returns the names of the symlinks in `<unit>.<subdir>` in every search path, as well as in
+    /// `<template>.<subdir>` for instances, without duplicates.
+    ///
+    /// Templates are instantiated with the instance of this unit, entries that are not valid unit names
+    /// are skipped.
     pub fn __parse_subdir(&self, subdir: &str) -> Vec<String> {
+        let (instance, names) = match unit_type(&self.filename) {
+            Ok(UnitType::Instance(_, instance, template)) => {
+                (Some(instance), vec![self.filename.to_string(), template])
+            }
+            _ => (None, vec![self.filename.to_string()]),
+        };
+
+        let suffix = self.filename.rsplit('.').next().unwrap_or_default();
+
         let mut result = Vec::new();
         for dir in (*self.paths).iter() {
-            let mut path = dir.to_owned();
-            let path_end = format!("{}.{}", self.filename, subdir);
-            path.push(path_end.as_str());
-            if let Ok(read_res) = read_dir(path) {
-                for item in read_res {
-                    if let Ok(entry) = item {
-                        if let Ok(meta) = entry.metadata() {
-                            if meta.is_symlink() {
-                                result.push(entry.file_name().to_string_lossy().to_string());
-                            }
+            for name in names.iter() {
+                let path = dir.join(format!("{}.{}", name, subdir));
+                let mut entries = match self.fs.read_dir(&path) {
+                    Ok(entries) => entries,
+                    Err(_) => continue,
+                };
+                entries.sort();
+                for entry in entries {
+                    if !self
+                        .fs
+                        .symlink_metadata(&entry)
+                        .is_ok_and(|x| x.file_type == FileType::Symlink)
+                    {
+                        continue;
+                    }
+                    let unit = match entry.file_name().and_then(|x| x.to_str()) {
+                        Some(unit) if is_valid_unit_name(unit, suffix) => unit,
+                        _ => {
+                            log::warn!(
+                                "{} is not a valid unit name, ignoring.",
+                                entry.to_string_lossy()
+                            );
+                            continue;
+                        }
+                    };
+                    let unit = match (unit_type(unit), instance) {
+                        (Ok(UnitType::Template(prefix)), Some(instance)) => {
+                            let suffix = unit.rsplit('.').next().unwrap_or_default();
+                            format!("{}@{}.{}", prefix, instance, suffix)
                         }
+                        (Ok(UnitType::Template(_)), None) => {
+                            log::warn!(
+                                "Cannot add template {} to non-instance unit {}, ignoring.",
+                                unit,
+                                self.filename
+                            );
+                            continue;
+                        }
+                        _ => unit.to_string(),
+                    };
+                    if !result.contains(&unit) {
+                        result.push(unit);
                     }
                 }
             }
@@ -183,3 +252,80 @@ impl SubdirParser {
         result
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        fs::MemoryFs,
+        parser::{SubdirParser, UnitParser},
+    };
+    use std::{
+        path::{Path, PathBuf},
+        rc::Rc,
+    };
+
+    #[test]
+    fn test_lex_once() {
+        let content = "[A]\nFoo=1\ninvalid\nBar=2\n[B]\nBaz=3\n[C]\nQux=4\ninvalid\n";
+        let path = Path::new("/etc/foo.service");
+
+        // consuming every entry
+        let mut errors = 0;
+        let mut entries = Vec::new();
+        for section in UnitParser::new(content, false, "foo.service", path).unwrap() {
+            let section = section.unwrap();
+            for entry in section {
+                match entry {
+                    Ok((key, _)) => entries.push(key),
+                    Err(_) => errors += 1,
+                }
+            }
+        }
+        assert_eq!(entries, vec!["Foo", "Bar", "Baz", "Qux"]);
+        assert_eq!(errors, 2);
+
+        // skipping sections, or leaving them halfway
+        let mut errors = 0;
+        let mut names = Vec::new();
+        for section in UnitParser::new(content, false, "foo.service", path).unwrap() {
+            match section {
+                Ok(mut section) => {
+                    names.push(section.name);
+                    assert!(section.next().unwrap().is_ok());
+                }
+                Err(_) => errors += 1,
+            }
+        }
+        assert_eq!(names, vec!["A", "B", "C"]);
+        assert_eq!(errors, 2);
+
+        // a section parser kept around does not steal entries from the next section
+        let mut parser = UnitParser::new(content, false, "foo.service", path).unwrap();
+        let mut first = parser.next().unwrap().unwrap();
+        let second = parser.next();
+        assert!(first.next().is_none());
+        assert!(second.unwrap().is_err());
+    }
+
+    #[test]
+    fn test_subdirs() {
+        let mut fs = MemoryFs::new();
+        fs.add_symlink("/etc/foo@bar.service.wants/a.service", "/lib/a.service")
+            .add_symlink("/etc/foo@.service.wants/b@.service", "/lib/b@.service")
+            .add_symlink("/lib/foo@.service.wants/a.service", "/lib/a.service")
+            .add_symlink("/lib/foo@.service.wants/invalid", "/lib/invalid")
+            .add_file("/lib/foo@.service.wants/c.service", "");
+        let paths = Rc::new(vec![PathBuf::from("/etc"), PathBuf::from("/lib")]);
+        let fs = Rc::new(fs);
+
+        let parser = SubdirParser::new(fs.clone(), Rc::clone(&paths), "foo@bar.service");
+        assert_eq!(
+            parser.__parse_subdir("wants"),
+            vec!["a.service", "b@bar.service"]
+        );
+        assert!(parser.__parse_subdir("requires").is_empty());
+
+        let parser = SubdirParser::new(fs, paths, "foo@.service");
+        assert_eq!(parser.__parse_subdir("wants"), vec!["a.service"]);
+    }
+}
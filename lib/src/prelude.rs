@@ -1,2 +1,15 @@
-pub use crate::config::{UnitConfig, UnitEntry, UnitSection};
+pub use crate::cache::UnitCache;
+pub use crate::config::{UnitConfig, UnitEntry, UnitFiles, UnitLoad, UnitSection};
+pub use crate::datetime::{
+    format_timestamp, format_timestamp_relative, parse_timestamp_at, TimestampStyle,
+};
+pub use crate::duration::TimeSpan;
+pub use crate::environment::{
+    merge_environment, parse_env_file, EnvironmentAssignments, EnvironmentFile,
+};
+pub use crate::exec::{expand_env, ExecCommand, ExecPrivileges};
+pub use crate::fs::{FileMetadata, FileType, MemoryFs, RootedFs, StdFs, UnitFs};
+pub use crate::search_paths::SearchPaths;
+pub use crate::size::{ByteSize, Limit};
+pub use crate::timer::{TimerContext, TimerSection, TimerSpec};
 pub use unit_parser_macro::{UnitConfig, UnitEntry, UnitSection};
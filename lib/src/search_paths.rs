@@ -0,0 +1,225 @@
+use std::{collections::HashMap, env, path::PathBuf};
+
+/// Builds the unit search paths of a system (`root`) or user service manager, in the same order as
+/// systemd looks units up.
+///
+/// Environment variables (`SYSTEMD_UNIT_PATH`, `HOME` and the `XDG_*` variables of user managers) are
+/// read when calling [`SearchPaths::build`], unless they were overridden with [`SearchPaths::var`].
+#[derive(Debug, Clone)]
+pub struct SearchPaths {
+    root: bool,
+    vars: HashMap<String, Option<String>>,
+}
+
+impl SearchPaths {
+    pub fn new(root: bool) -> Self {
+        Self {
+            root,
+            vars: HashMap::new(),
+        }
+    }
+
+    /// Overrides an environment variable, or unsets it if `value` is `None`.
+    pub fn var<K: Into<String>, V: Into<String>>(mut self, key: K, value: Option<V>) -> Self {
+        self.vars.insert(key.into(), value.map(Into::into));
+        self
+    }
+
+    fn get(&self, key: &str) -> Option<String> {
+        match self.vars.get(key) {
+            Some(value) => value.clone(),
+            None => env::var(key).ok(),
+        }
+        .filter(|x| !x.is_empty())
+    }
+
+    /// Returns the search paths, ordered from the highest priority to the lowest.
+    ///
+    /// If `SYSTEMD_UNIT_PATH` is set, only the paths listed in it are used, unless it ends with a `:`, in
+    /// which case the default paths are appended.
+    pub fn build(&self) -> Vec<PathBuf> {
+        let mut result: Vec<PathBuf> = Vec::new();
+        let append = match self.get("SYSTEMD_UNIT_PATH") {
+            Some(value) => {
+                result.extend(
+                    value
+                        .split(':')
+                        .filter(|x| !x.is_empty())
+                        .map(PathBuf::from),
+                );
+                value.ends_with(':')
+            }
+            None => true,
+        };
+        if append {
+            result.extend(match self.root {
+                true => Self::system_paths(),
+                false => self.user_paths(),
+            });
+        }
+
+        let mut seen = Vec::new();
+        result.retain(|x| {
+            let new = !seen.contains(x);
+            if new {
+                seen.push(x.clone());
+            }
+            new
+        });
+        result
+    }
+
+    fn system_paths() -> Vec<PathBuf> {
+        [
+            "/etc/systemd/system.control",
+            "/run/systemd/system.control",
+            "/run/systemd/transient",
+            "/run/systemd/generator.early",
+            "/etc/systemd/system",
+            "/etc/systemd/system.attached",
+            "/run/systemd/system",
+            "/run/systemd/system.attached",
+            "/run/systemd/generator",
+            "/usr/local/lib/systemd/system",
+            "/usr/lib/systemd/system",
+            "/run/systemd/generator.late",
+        ]
+        .into_iter()
+        .map(PathBuf::from)
+        .collect()
+    }
+
+    // see `user_dirs()` in systemd's `lookup-paths.c`; directories whose variables are missing are left out
+    fn user_paths(&self) -> Vec<PathBuf> {
+        let home = self.get("HOME").map(PathBuf::from);
+        let config_home = self
+            .get("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| home.as_ref().map(|x| x.join(".config")));
+        let data_home = self
+            .get("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| home.as_ref().map(|x| x.join(".local/share")));
+        let runtime = self.get("XDG_RUNTIME_DIR").map(PathBuf::from);
+        let config_dirs = self
+            .get("XDG_CONFIG_DIRS")
+            .unwrap_or_else(|| "/etc/xdg".to_string());
+        let data_dirs = self
+            .get("XDG_DATA_DIRS")
+            .unwrap_or_else(|| "/usr/local/share:/usr/share".to_string());
+
+        let config = |x: &str| config_home.as_ref().map(|home| home.join(x));
+        let runtime = |x: &str| runtime.as_ref().map(|dir| dir.join(x));
+        let xdg = |dirs: &str| {
+            dirs.split(':')
+                .filter(|x| !x.is_empty())
+                .map(|x| PathBuf::from(x).join("systemd/user"))
+                .collect::<Vec<_>>()
+        };
+
+        let mut result: Vec<PathBuf> = [
+            config("systemd/user.control"),
+            runtime("systemd/user.control"),
+            runtime("systemd/transient"),
+            runtime("systemd/generator.early"),
+            config("systemd/user"),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        result.extend(xdg(&config_dirs));
+        result.push(PathBuf::from("/etc/systemd/user"));
+        result.extend(runtime("systemd/user"));
+        result.push(PathBuf::from("/run/systemd/user"));
+        result.extend(runtime("systemd/generator"));
+        result.extend(data_home.map(|x| x.join("systemd/user")));
+        result.extend(xdg(&data_dirs));
+        result.extend(
+            [
+                "/usr/local/lib/systemd/user",
+                "/usr/local/share/systemd/user",
+                "/usr/lib/systemd/user",
+                "/usr/share/systemd/user",
+            ]
+            .into_iter()
+            .map(PathBuf::from),
+        );
+        result.extend(runtime("systemd/generator.late"));
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::search_paths::SearchPaths;
+    use std::path::PathBuf;
+
+    fn paths(search_paths: SearchPaths) -> Vec<String> {
+        search_paths
+            .build()
+            .into_iter()
+            .map(|x| x.to_string_lossy().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_system() {
+        let system = SearchPaths::new(true).var("SYSTEMD_UNIT_PATH", None::<String>);
+        let default = paths(system.clone());
+        assert_eq!(default.len(), 12);
+        assert_eq!(default[0], "/etc/systemd/system.control");
+        assert_eq!(default[4], "/etc/systemd/system");
+        assert_eq!(default[11], "/run/systemd/generator.late");
+
+        assert_eq!(
+            paths(system.clone().var("SYSTEMD_UNIT_PATH", Some("/foo:/bar"))),
+            vec!["/foo", "/bar"]
+        );
+        let appended = paths(system.var("SYSTEMD_UNIT_PATH", Some("/foo::/etc/systemd/system:")));
+        assert_eq!(
+            appended[..3],
+            ["/foo", "/etc/systemd/system", "/etc/systemd/system.control"]
+        );
+        assert_eq!(appended.len(), 13);
+    }
+
+    #[test]
+    fn test_user() {
+        let user = SearchPaths::new(false)
+            .var("SYSTEMD_UNIT_PATH", None::<String>)
+            .var("HOME", Some("/home/foo"))
+            .var("XDG_CONFIG_HOME", None::<String>)
+            .var("XDG_DATA_HOME", None::<String>)
+            .var("XDG_CONFIG_DIRS", None::<String>)
+            .var("XDG_DATA_DIRS", Some("/usr/share:/opt/share"))
+            .var("XDG_RUNTIME_DIR", Some("/run/user/1000"));
+        assert_eq!(
+            paths(user.clone()),
+            vec![
+                "/home/foo/.config/systemd/user.control",
+                "/run/user/1000/systemd/user.control",
+                "/run/user/1000/systemd/transient",
+                "/run/user/1000/systemd/generator.early",
+                "/home/foo/.config/systemd/user",
+                "/etc/xdg/systemd/user",
+                "/etc/systemd/user",
+                "/run/user/1000/systemd/user",
+                "/run/systemd/user",
+                "/run/user/1000/systemd/generator",
+                "/home/foo/.local/share/systemd/user",
+                "/usr/share/systemd/user",
+                "/opt/share/systemd/user",
+                "/usr/local/lib/systemd/user",
+                "/usr/local/share/systemd/user",
+                "/usr/lib/systemd/user",
+                "/run/user/1000/systemd/generator.late",
+            ]
+        );
+
+        let without_home = user
+            .var("HOME", None::<String>)
+            .var("XDG_RUNTIME_DIR", None::<String>)
+            .build();
+        assert_eq!(without_home[0], PathBuf::from("/etc/xdg/systemd/user"));
+    }
+}
@@ -0,0 +1,251 @@
+use crate::{config::UnitEntry, error::Error, extract_word::WHITESPACE};
+use std::fmt;
+
+// the suffixes accepted by systemd's `parse_size()` with base 1024, longest factor first
+const SUFFIXES: [(char, u64); 7] = [
+    ('E', 1 << 60),
+    ('P', 1 << 50),
+    ('T', 1 << 40),
+    ('G', 1 << 30),
+    ('M', 1 << 20),
+    ('K', 1 << 10),
+    ('B', 1),
+];
+
+/// A size in bytes, such as `512M` or `1.5G`.
+///
+/// Suffixes are base 1024 (`K`, `M`, `G`, `T`, `P` and `E`, as well as `B` for bytes); a number without a
+/// suffix is a number of bytes. Like in systemd, fractions are allowed with a suffix other than `B` and
+/// multiple parts are added up, so `1G 512M` is the same as `1.5G`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct ByteSize(pub u64);
+
+impl UnitEntry for ByteSize {
+    type Error = Error;
+    fn parse_from_str<S: AsRef<str>>(input: S) -> std::result::Result<Self, Self::Error> {
+        let input = input.as_ref();
+        let error = || Error::InvalidSizeError {
+            size: input.to_string(),
+        };
+
+        let mut rest = input.trim_matches(|x| WHITESPACE.contains(x));
+        if rest.is_empty() {
+            return Err(error());
+        }
+        let mut result: u64 = 0;
+        while !rest.is_empty() {
+            let integer_len = rest
+                .find(|x: char| !x.is_ascii_digit())
+                .unwrap_or(rest.len());
+            let (integer, after) = rest.split_at(integer_len);
+            let (fraction, after) = match after.strip_prefix('.') {
+                Some(after) => {
+                    let (fraction, after) = after.split_at(
+                        after
+                            .find(|x: char| !x.is_ascii_digit())
+                            .unwrap_or(after.len()),
+                    );
+                    (Some(fraction), after)
+                }
+                None => (None, after),
+            };
+            if integer.is_empty() {
+                return Err(error());
+            }
+            let after = after.trim_start_matches(|x| WHITESPACE.contains(x));
+            let (factor, after) = match SUFFIXES.iter().find(|(x, _)| after.starts_with(*x)) {
+                Some((_, factor)) => (*factor, &after[1..]),
+                None => (1, after),
+            };
+
+            let fraction = match fraction {
+                // bytes cannot be split
+                Some(_) if factor == 1 => return Err(error()),
+                Some(fraction) => fraction,
+                None => "",
+            };
+
+            let integer: u64 = integer.parse().map_err(|_| error())?;
+            // fractions smaller than a byte are dropped
+            let fraction = fraction
+                .bytes()
+                .take(18)
+                .enumerate()
+                .fold(0u128, |acc, (i, x)| {
+                    acc + (x - b'0') as u128 * factor as u128 * 10u128.pow(17 - i as u32)
+                })
+                / 10u128.pow(18);
+            result = integer
+                .checked_mul(factor)
+                .and_then(|x| x.checked_add(fraction as u64))
+                .and_then(|x| x.checked_add(result))
+                .ok_or_else(error)?;
+            rest = after.trim_start_matches(|x| WHITESPACE.contains(x));
+        }
+        Ok(Self(result))
+    }
+}
+
+impl fmt::Display for ByteSize {
+    /// Formats the size like systemd's `format_bytes()`, e.g. `1.5G`, with one decimal at most.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (suffix, factor) in SUFFIXES.iter().take(SUFFIXES.len() - 1) {
+            if self.0 >= *factor {
+                let tenths = (self.0 % factor) * 10 / factor;
+                return match tenths {
+                    0 => write!(f, "{}{}", self.0 / factor, suffix),
+                    tenths => write!(f, "{}.{}{}", self.0 / factor, tenths, suffix),
+                };
+            }
+        }
+        write!(f, "{}B", self.0)
+    }
+}
+
+/// A limit such as `MemoryMax=` or `LimitNOFILE=`, which may be `infinity`, a [`ByteSize`] or a percentage.
+///
+/// Percentages (`%`, `‰` or `‱`) are relative to a total that depends on the setting, such as the
+/// physical memory, and are kept as permyriads until [`Limit::resolve`] is called with that total.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Limit {
+    Infinity,
+    Bytes(u64),
+    /// In units of 1/10000 of the total.
+    Permyriad(u32),
+}
+
+impl UnitEntry for Limit {
+    type Error = Error;
+    fn parse_from_str<S: AsRef<str>>(input: S) -> std::result::Result<Self, Self::Error> {
+        let input = input.as_ref();
+        let trimmed = input.trim_matches(|x| WHITESPACE.contains(x));
+        if trimmed == "infinity" {
+            return Ok(Self::Infinity);
+        }
+        let percentage = [('%', 100), ('‰', 10), ('‱', 1)]
+            .into_iter()
+            .find_map(|(suffix, factor)| Some((trimmed.strip_suffix(suffix)?, factor)));
+        match percentage {
+            Some((value, factor)) => parse_permyriad(value, factor)
+                .filter(|x| *x <= 10000)
+                .map(Self::Permyriad)
+                .ok_or_else(|| Error::InvalidSizeError {
+                    size: input.to_string(),
+                }),
+            None => Ok(Self::Bytes(ByteSize::parse_from_str(input)?.0)),
+        }
+    }
+}
+
+// parses `value` in units of `factor` permyriads, allowing as many decimals as make up whole permyriads
+fn parse_permyriad(value: &str, factor: u32) -> Option<u32> {
+    let (integer, fraction) = value.split_once('.').unwrap_or((value, ""));
+    let decimals = factor.ilog10() as usize;
+    if integer.is_empty()
+        || fraction.len() > decimals
+        || !integer
+            .bytes()
+            .chain(fraction.bytes())
+            .all(|x| x.is_ascii_digit())
+    {
+        return None;
+    }
+    let fraction = format!("{:0<width$}", fraction, width = decimals);
+    let fraction: u32 = match fraction.as_str() {
+        "" => 0,
+        fraction => fraction.parse().ok()?,
+    };
+    integer
+        .parse::<u32>()
+        .ok()?
+        .checked_mul(factor)?
+        .checked_add(fraction)
+}
+
+impl Limit {
+    /// Returns the limit in bytes (or whatever `total` is measured in), or `None` if it is `infinity`.
+    pub fn resolve(&self, total: u64) -> Option<u64> {
+        match self {
+            Limit::Infinity => None,
+            Limit::Bytes(bytes) => Some(*bytes),
+            Limit::Permyriad(permyriad) => {
+                Some((total as u128 * *permyriad as u128 / 10000) as u64)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Limit::Infinity => write!(f, "infinity"),
+            Limit::Bytes(bytes) => write!(f, "{}", ByteSize(*bytes)),
+            Limit::Permyriad(permyriad) if permyriad % 100 == 0 => {
+                write!(f, "{}%", permyriad / 100)
+            }
+            Limit::Permyriad(permyriad) => {
+                let decimals = format!("{:02}", permyriad % 100);
+                write!(f, "{}.{}%", permyriad / 100, decimals.trim_end_matches('0'))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        config::UnitEntry,
+        size::{ByteSize, Limit},
+    };
+
+    #[test]
+    fn test_byte_size() {
+        let pairs = [
+            ("1024", 1024),
+            ("512M", 512 << 20),
+            ("1.5G", 3 << 29),
+            ("1G 512M", 3 << 29),
+            ("2 K", 2048),
+            ("0.5K", 512),
+        ];
+        for (input, bytes) in pairs {
+            assert_eq!(ByteSize::parse_from_str(input).unwrap(), ByteSize(bytes));
+        }
+        for input in [
+            "", "16E", "1X", "-1K", "K", ".5K", "1.2.3", "1.5", "1.5B", "1G 0.5",
+        ] {
+            assert!(ByteSize::parse_from_str(input).is_err(), "{}", input);
+        }
+
+        assert_eq!(ByteSize(3 << 29).to_string(), "1.5G");
+        assert_eq!(ByteSize(512 << 20).to_string(), "512M");
+        assert_eq!(ByteSize(1000).to_string(), "1000B");
+        assert_eq!(ByteSize(1 << 60).to_string(), "1E");
+    }
+
+    #[test]
+    fn test_limit() {
+        assert_eq!(Limit::parse_from_str("infinity").unwrap(), Limit::Infinity);
+        assert_eq!(Limit::parse_from_str("1K").unwrap(), Limit::Bytes(1024));
+        assert_eq!(
+            Limit::parse_from_str("50%").unwrap(),
+            Limit::Permyriad(5000)
+        );
+        assert_eq!(
+            Limit::parse_from_str("12.5%").unwrap(),
+            Limit::Permyriad(1250)
+        );
+        assert_eq!(Limit::parse_from_str("5‰").unwrap(), Limit::Permyriad(50));
+        assert_eq!(Limit::parse_from_str("3‱").unwrap(), Limit::Permyriad(3));
+        for input in ["101%", "1.234%", "%", "x%", "infinite"] {
+            assert!(Limit::parse_from_str(input).is_err(), "{}", input);
+        }
+
+        assert_eq!(Limit::Permyriad(2500).resolve(4 << 30), Some(1 << 30));
+        assert_eq!(Limit::Bytes(10).resolve(4 << 30), Some(10));
+        assert_eq!(Limit::Infinity.resolve(4 << 30), None);
+        assert_eq!(Limit::Permyriad(1250).to_string(), "12.5%");
+        assert_eq!(Limit::Permyriad(5000).to_string(), "50%");
+        assert_eq!(Limit::Bytes(2048).to_string(), "2K");
+    }
+}
@@ -1,6 +1,6 @@
 use crate::error::Error;
 use crate::escape::escape;
-use crate::template::{unit_type, UnitType};
+use crate::template::{unit_prefix, unit_type, UnitType};
 use nix::sys::utsname::UtsName;
 use nix::unistd::{Uid, User};
 use nix::{
@@ -19,8 +19,8 @@ static BOOT_ID: Lazy<String> = Lazy::new(|| {
 });
 static MACHINE_ID: Lazy<String> =
     Lazy::new(|| fs::read_to_string("/etc/machine-id").expect("Failed to read machine_id."));
-static CURRENT_UID: Lazy<Uid> = Lazy::new(|| Uid::current());
-static CURRENT_GID: Lazy<Gid> = Lazy::new(|| Gid::current());
+static CURRENT_UID: Lazy<Uid> = Lazy::new(Uid::current);
+static CURRENT_GID: Lazy<Gid> = Lazy::new(Gid::current);
 
 // return Cow?
 pub(crate) fn resolve(
@@ -88,7 +88,7 @@ pub(crate) fn resolve(
         }
         'G' => {
             if root {
-                result.push_str("0");
+                result.push('0');
             } else {
                 result.push_str(&CURRENT_GID.to_string());
             }
@@ -100,7 +100,7 @@ pub(crate) fn resolve(
                 if let Ok(res) = env::var("HOME") {
                     result.push_str(&res);
                 } else {
-                    result.push_str("~");
+                    result.push('~');
                 }
             }
         }
@@ -110,51 +110,29 @@ pub(crate) fn resolve(
             }
         }
         'i' => {
-            if let UnitType::Instance(instance_name, _) = unit_type(filename)? {
+            if let UnitType::Instance(_, instance_name, _) = unit_type(filename)? {
                 result.push_str(&escape(instance_name));
             }
         }
         'I' => {
-            if let UnitType::Instance(instance_name, _) = unit_type(filename)? {
+            if let UnitType::Instance(_, instance_name, _) = unit_type(filename)? {
                 result.push_str(instance_name);
             }
         }
         'j' => {
-            if let UnitType::Instance(instance_name, _) = unit_type(filename)? {
-                result.push_str(&escape(instance_name.split('-').last().unwrap()));
-            } else {
-                result.push_str(&escape(
-                    filename
-                        .split('.')
-                        .nth(0)
-                        .unwrap()
-                        .split('-')
-                        .last()
-                        .unwrap(),
-                ));
-            }
+            let prefix = unit_prefix(filename)?;
+            result.push_str(&escape(prefix.rsplit('-').next().unwrap()));
         }
         'J' => {
-            if let UnitType::Instance(instance_name, _) = unit_type(filename)? {
-                result.push_str(instance_name.split('-').last().unwrap());
-            } else {
-                result.push_str(
-                    filename
-                        .split('.')
-                        .nth(0)
-                        .unwrap()
-                        .split('-')
-                        .last()
-                        .unwrap(),
-                );
-            }
+            let prefix = unit_prefix(filename)?;
+            result.push_str(prefix.rsplit('-').next().unwrap());
         }
         'l' => result.push_str(
             UTS_NAME
                 .nodename()
                 .to_string_lossy()
                 .split('.')
-                .nth(0)
+                .next()
                 .unwrap(),
         ),
         'L' => {
@@ -172,32 +150,22 @@ pub(crate) fn resolve(
         'm' => result.push_str(&MACHINE_ID),
         'M' => {
             if let Some(res) = OS_RELEASE.extra.get("IMAGE_ID") {
-                result.push_str(&res)
+                result.push_str(res)
             }
         }
         'n' => result.push_str(&escape(filename)),
-        'N' => result.push_str(&escape(filename.split(".").nth(0).unwrap())),
+        'N' => result.push_str(&escape(
+            filename.rsplit_once('.').map_or(filename, |(name, _)| name),
+        )),
         'o' => result.push_str(&OS_RELEASE.id),
-        'p' => {
-            if let UnitType::Instance(instance_name, _) = unit_type(filename)? {
-                result.push_str(&escape(instance_name));
-            } else {
-                result.push_str(&escape(filename.split('.').nth(0).unwrap()));
-            }
-        }
-        'P' => {
-            if let UnitType::Instance(instance_name, _) = unit_type(filename)? {
-                result.push_str(instance_name);
-            } else {
-                result.push_str(filename.split('.').nth(0).unwrap());
-            }
-        }
+        'p' => result.push_str(&escape(unit_prefix(filename)?)),
+        'P' => result.push_str(unit_prefix(filename)?),
         'q' => result.push_str(
             UTS_NAME
                 .nodename()
                 .to_string_lossy()
                 .split('.')
-                .nth(0)
+                .next()
                 .unwrap(),
         ),
         's' => {
@@ -264,17 +232,17 @@ pub(crate) fn resolve(
         'w' => result.push_str(&OS_RELEASE.version_id),
         'W' => {
             if let Some(res) = OS_RELEASE.extra.get("VARIANT_ID") {
-                result.push_str(&res);
+                result.push_str(res);
             }
         }
         'y' => {
             if let Some(res) = path.to_str() {
-                result.push_str(&res)
+                result.push_str(res)
             }
         }
         'Y' => {
             if let Some(res) = path.parent().expect("Invalid file path.").to_str() {
-                result.push_str(&res)
+                result.push_str(res)
             }
         }
         '%' => result.push('%'),
@@ -282,3 +250,41 @@ pub(crate) fn resolve(
     };
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::specifiers::resolve;
+    use std::path::Path;
+
+    fn resolve_all(specifiers: &str, filename: &str) -> String {
+        let mut result = String::new();
+        for specifier in specifiers.chars() {
+            if !result.is_empty() {
+                result.push('|');
+            }
+            resolve(
+                &mut result,
+                specifier,
+                true,
+                filename,
+                Path::new("/etc/systemd/system"),
+            )
+            .unwrap();
+        }
+        result
+    }
+
+    #[test]
+    fn test_unit_names() {
+        assert_eq!(
+            resolve_all("pijPIJ", "foo-bar@baz.service"),
+            "foo-bar|baz|bar|foo-bar|baz|bar"
+        );
+        assert_eq!(resolve_all("pjN", "foo-bar.service"), "foo-bar|bar|foo-bar");
+        assert_eq!(resolve_all("pj", "foo-bar@.service"), "foo-bar|bar");
+        assert_eq!(
+            resolve_all("pPjJN", "foo-bar.baz.service"),
+            "foo-bar.baz|foo-bar.baz|bar.baz|bar.baz|foo-bar.baz"
+        );
+    }
+}
@@ -1,30 +1,115 @@
 use crate::{config::Result, error::Error};
 
 pub(crate) enum UnitType<'a> {
-    Template(&'a str),         // template name
-    Instance(&'a str, String), // instance name, template file name
-    Regular(&'a str),          // unit name
+    Template(&'a str),                  // template name
+    Instance(&'a str, &'a str, String), // template name, instance name, template file name
+    Regular(&'a str),                   // unit name
 }
 
 pub(crate) fn unit_type<'a>(filename: &'a str) -> Result<UnitType<'a>> {
-    let split: Vec<&str> = filename.split("@").collect();
-    match split.len() {
-        1 => Ok(UnitType::Regular(filename)),
-        2 => {
-            if split.get(1).unwrap().starts_with('.') {
-                Ok(UnitType::Template(split.get(0).unwrap()))
-            } else {
-                let mut sub_split = split.get(1).unwrap().split('.');
-                let template_name = sub_split.nth(0).unwrap();
-                let suffix = sub_split.last().unwrap();
-                Ok(UnitType::Instance(
-                    split.get(0).unwrap(),
-                    format!("{}@.{}", template_name, suffix),
-                ))
-            }
-        }
+    let (prefix, rest) = match filename.split_once('@') {
+        Some(split) => split,
+        None => return Ok(UnitType::Regular(filename)),
+    };
+    match rest.rsplit_once('.') {
+        Some(("", _)) => Ok(UnitType::Template(prefix)),
+        Some((instance, suffix)) if !prefix.is_empty() => Ok(UnitType::Instance(
+            prefix,
+            instance,
+            format!("{}@.{}", prefix, suffix),
+        )),
         _ => Err(Error::InvalidFilenameError {
             filename: filename.to_string(),
         }),
     }
 }
+
+// the part before `@` for templates and instances, the name without its suffix otherwise, same as
+// systemd's `unit_name_to_prefix`
+pub(crate) fn unit_prefix(filename: &str) -> Result<&str> {
+    Ok(match unit_type(filename)? {
+        UnitType::Template(prefix) | UnitType::Instance(prefix, _, _) => prefix,
+        UnitType::Regular(name) => name.rsplit_once('.').map_or(name, |(prefix, _)| prefix),
+    })
+}
+
+const UNIT_SUFFIXES: [&str; 11] = [
+    "service",
+    "socket",
+    "device",
+    "mount",
+    "automount",
+    "swap",
+    "target",
+    "path",
+    "timer",
+    "slice",
+    "scope",
+];
+
+// same as systemd's `unit_name_is_valid(name, UNIT_NAME_ANY)`, but also accepts `suffix`, which is the
+// suffix of the units being loaded
+pub(crate) fn is_valid_unit_name(name: &str, suffix: &str) -> bool {
+    let valid = |x: &str, extra: &str| {
+        x.chars()
+            .all(|x| x.is_ascii_alphanumeric() || ":-_.\\".contains(x) || extra.contains(x))
+    };
+    let (name_prefix, name_suffix) = match name.rsplit_once('.') {
+        Some(split) if name.len() < 256 => split,
+        _ => return false,
+    };
+    if name_suffix != suffix && !UNIT_SUFFIXES.contains(&name_suffix) {
+        return false;
+    }
+    match name_prefix.split_once('@') {
+        Some((prefix, instance)) => !prefix.is_empty() && valid(prefix, "") && valid(instance, "@"),
+        None => !name_prefix.is_empty() && valid(name_prefix, ""),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::template::{is_valid_unit_name, unit_prefix, unit_type, UnitType};
+
+    #[test]
+    fn test_unit_type() {
+        assert!(matches!(
+            unit_type("foo.service").unwrap(),
+            UnitType::Regular("foo.service")
+        ));
+        assert!(matches!(
+            unit_type("getty@.service").unwrap(),
+            UnitType::Template("getty")
+        ));
+        match unit_type("getty@tty1.example.service").unwrap() {
+            UnitType::Instance(prefix, instance, template) => {
+                assert_eq!(prefix, "getty");
+                assert_eq!(instance, "tty1.example");
+                assert_eq!(template, "getty@.service");
+            }
+            _ => panic!(),
+        }
+        assert!(unit_type("@foo.service").is_err());
+
+        assert_eq!(unit_prefix("foo-bar@baz.service").unwrap(), "foo-bar");
+        assert_eq!(unit_prefix("foo-bar@.service").unwrap(), "foo-bar");
+        assert_eq!(unit_prefix("foo-bar.service").unwrap(), "foo-bar");
+        assert_eq!(unit_prefix("foo.bar.service").unwrap(), "foo.bar");
+    }
+
+    #[test]
+    fn test_unit_name() {
+        assert!(is_valid_unit_name("foo.service", "service"));
+        assert!(is_valid_unit_name("getty@.service", "service"));
+        assert!(is_valid_unit_name(
+            "systemd-fsck@dev-disk-by\\x2duuid-1.service",
+            "service"
+        ));
+        assert!(!is_valid_unit_name("foo", "service"));
+        assert!(!is_valid_unit_name("foo.conf", "service"));
+        assert!(is_valid_unit_name("foo.conf", "conf"));
+        assert!(!is_valid_unit_name(".service", "service"));
+        assert!(!is_valid_unit_name("@foo.service", "service"));
+        assert!(!is_valid_unit_name("foo bar.service", "service"));
+    }
+}
@@ -0,0 +1,296 @@
+use chrono::{DateTime, Duration, Utc};
+use unit_parser_macro::UnitSection;
+
+/// The entries of the `[Timer]` section of a `.timer` unit that [`TimerSpec`] is built from.
+///
+/// It can be used as a section of a unit directly, or flattened into a section with more entries.
+#[derive(UnitSection, Debug, Clone, PartialEq, Eq)]
+pub struct TimerSection {
+    #[entry(key = "OnActiveSec", multiple)]
+    pub on_active: Vec<Duration>,
+    #[entry(key = "OnBootSec", multiple)]
+    pub on_boot: Vec<Duration>,
+    #[entry(key = "OnStartupSec", multiple)]
+    pub on_startup: Vec<Duration>,
+    #[entry(key = "OnUnitActiveSec", multiple)]
+    pub on_unit_active: Vec<Duration>,
+    #[entry(key = "OnUnitInactiveSec", multiple)]
+    pub on_unit_inactive: Vec<Duration>,
+    #[entry(key = "AccuracySec", default = Duration::minutes(1))]
+    pub accuracy: Duration,
+    #[entry(key = "RandomizedDelaySec", default = Duration::zero())]
+    pub randomized_delay: Duration,
+    #[entry(key = "FixedRandomDelay", default = false)]
+    pub fixed_random_delay: bool,
+    #[entry(key = "Persistent", default = false)]
+    pub persistent: bool,
+}
+
+/// The monotonic triggers and delays of a `.timer` unit, from `OnBootSec=` and similar entries.
+///
+/// It is usually built from a parsed [`TimerSection`], but the fields can also be filled by hand.
+///
+/// `OnCalendar=` and `Persistent=`, which only applies to calendar triggers, are not interpreted by
+/// [`TimerSpec::next_elapse`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimerSpec {
+    /// `OnActiveSec=`: relative to when the timer unit was activated.
+    pub on_active: Vec<Duration>,
+    /// `OnBootSec=`: relative to when the system booted.
+    pub on_boot: Vec<Duration>,
+    /// `OnStartupSec=`: relative to when the service manager started.
+    pub on_startup: Vec<Duration>,
+    /// `OnUnitActiveSec=`: relative to when the triggered unit was last activated.
+    pub on_unit_active: Vec<Duration>,
+    /// `OnUnitInactiveSec=`: relative to when the triggered unit was last deactivated.
+    pub on_unit_inactive: Vec<Duration>,
+    /// `AccuracySec=`: the timer may elapse up to this much later, to coalesce wake-ups.
+    pub accuracy: Duration,
+    /// `RandomizedDelaySec=`: elapses are delayed by a random time up to this long.
+    pub randomized_delay: Duration,
+    /// `FixedRandomDelay=`: the random delay is the same every time.
+    pub fixed_random_delay: bool,
+    /// `Persistent=`
+    pub persistent: bool,
+}
+
+impl Default for TimerSpec {
+    fn default() -> Self {
+        Self {
+            on_active: Vec::new(),
+            on_boot: Vec::new(),
+            on_startup: Vec::new(),
+            on_unit_active: Vec::new(),
+            on_unit_inactive: Vec::new(),
+            accuracy: Duration::minutes(1),
+            randomized_delay: Duration::zero(),
+            fixed_random_delay: false,
+            persistent: false,
+        }
+    }
+}
+
+impl From<&TimerSection> for TimerSpec {
+    fn from(section: &TimerSection) -> Self {
+        Self {
+            on_active: section.on_active.clone(),
+            on_boot: section.on_boot.clone(),
+            on_startup: section.on_startup.clone(),
+            on_unit_active: section.on_unit_active.clone(),
+            on_unit_inactive: section.on_unit_inactive.clone(),
+            accuracy: section.accuracy,
+            randomized_delay: section.randomized_delay,
+            fixed_random_delay: section.fixed_random_delay,
+            persistent: section.persistent,
+        }
+    }
+}
+
+/// The state of a timer and its unit that [`TimerSpec::next_elapse`] depends on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimerContext {
+    pub now: DateTime<Utc>,
+    pub boot: DateTime<Utc>,
+    /// When the service manager started, which is later than `boot`.
+    pub startup: DateTime<Utc>,
+    /// When the timer unit was activated.
+    pub activated: DateTime<Utc>,
+    /// When the timer last elapsed, if ever.
+    pub last_trigger: Option<DateTime<Utc>>,
+    /// When the triggered unit was last activated, if ever.
+    pub unit_active: Option<DateTime<Utc>>,
+    /// When the triggered unit was last deactivated, if ever.
+    pub unit_inactive: Option<DateTime<Utc>>,
+    /// Picks the delay within `RandomizedDelaySec=`, and should be random every time.
+    pub random: u64,
+    /// Used instead of `random` with `FixedRandomDelay=`. systemd hashes the machine ID, the user and the
+    /// unit name, so that the delay is stable but differs between timers and machines.
+    pub fixed_seed: u64,
+}
+
+impl TimerSpec {
+    /// Computes when the timer elapses next, like systemd's `timer_enter_waiting()` for monotonic
+    /// triggers, or `None` if no trigger is left.
+    ///
+    /// The earliest trigger wins. Triggers relative to the triggered unit are relative to the later of
+    /// its last (de)activation and the last trigger, and only count once either happened. Triggers relative to
+    /// boot, startup or activation elapse only once: if the timer already elapsed before, those in the
+    /// past are skipped, otherwise they elapse immediately (the result may be earlier than `now`). The random
+    /// delay is then added, and the timer may elapse up to `accuracy` after the result.
+    pub fn next_elapse(&self, context: &TimerContext) -> Option<DateTime<Utc>> {
+        let once = [
+            (&self.on_active, Some(context.activated)),
+            (&self.on_boot, Some(context.boot)),
+            (&self.on_startup, Some(context.startup)),
+        ];
+        // `None` is less than any `Some`
+        let max = |x: Option<DateTime<Utc>>| x.max(context.last_trigger);
+        let repeated = [
+            (&self.on_unit_active, max(context.unit_active)),
+            (&self.on_unit_inactive, max(context.unit_inactive)),
+        ];
+
+        let mut result: Option<DateTime<Utc>> = None;
+        for (one_time, (values, base)) in once
+            .into_iter()
+            .map(|x| (true, x))
+            .chain(repeated.into_iter().map(|x| (false, x)))
+        {
+            let base = match base {
+                Some(base) => base,
+                None => continue,
+            };
+            for value in values.iter() {
+                let elapse = base + *value;
+                if one_time && context.last_trigger.is_some() && elapse < context.now {
+                    continue;
+                }
+                result = Some(result.map_or(elapse, |x| x.min(elapse)));
+            }
+        }
+        result.map(|x| x + self.random_delay(context))
+    }
+
+    fn random_delay(&self, context: &TimerContext) -> Duration {
+        let range = match self.randomized_delay.num_microseconds() {
+            Some(range) if range > 0 => range as u64,
+            _ => return Duration::zero(),
+        };
+        let random = match self.fixed_random_delay {
+            true => context.fixed_seed,
+            false => context.random,
+        };
+        Duration::microseconds((random % range) as i64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        config::UnitSection,
+        parser::UnitParser,
+        timer::{TimerContext, TimerSection, TimerSpec},
+    };
+    use chrono::{DateTime, Duration, TimeZone, Utc};
+    use std::path::Path;
+
+    fn context(now: DateTime<Utc>) -> TimerContext {
+        let boot = Utc.with_ymd_and_hms(2023, 1, 1, 0, 0, 0).unwrap();
+        TimerContext {
+            now,
+            boot,
+            startup: boot + Duration::seconds(2),
+            activated: boot + Duration::seconds(10),
+            last_trigger: None,
+            unit_active: None,
+            unit_inactive: None,
+            random: 0,
+            fixed_seed: 0,
+        }
+    }
+
+    #[test]
+    fn test_one_time() {
+        let boot = Utc.with_ymd_and_hms(2023, 1, 1, 0, 0, 0).unwrap();
+        let spec = TimerSpec {
+            on_boot: vec![Duration::minutes(15), Duration::hours(1)],
+            on_startup: vec![Duration::minutes(30)],
+            ..Default::default()
+        };
+
+        let mut context = context(boot + Duration::minutes(1));
+        assert_eq!(
+            spec.next_elapse(&context),
+            Some(boot + Duration::minutes(15))
+        );
+
+        // already elapsed once, the remaining triggers are still ahead
+        context.now = boot + Duration::minutes(20);
+        context.last_trigger = Some(boot + Duration::minutes(15));
+        assert_eq!(
+            spec.next_elapse(&context),
+            Some(boot + Duration::seconds(2) + Duration::minutes(30))
+        );
+
+        context.now = boot + Duration::hours(2);
+        context.last_trigger = Some(boot + Duration::hours(1));
+        assert_eq!(spec.next_elapse(&context), None);
+
+        // never elapsed, so a trigger in the past elapses right away
+        context.last_trigger = None;
+        assert_eq!(
+            spec.next_elapse(&context),
+            Some(boot + Duration::minutes(15))
+        );
+    }
+
+    #[test]
+    fn test_unit_relative() {
+        let boot = Utc.with_ymd_and_hms(2023, 1, 1, 0, 0, 0).unwrap();
+        let spec = TimerSpec {
+            on_active: vec![Duration::seconds(5)],
+            on_unit_active: vec![Duration::hours(1)],
+            randomized_delay: Duration::minutes(10),
+            ..Default::default()
+        };
+
+        let mut context = context(boot + Duration::minutes(1));
+        assert_eq!(
+            spec.next_elapse(&context),
+            Some(boot + Duration::seconds(15))
+        );
+
+        // the unit never ran, but the timer elapsed before
+        context.last_trigger = Some(boot + Duration::seconds(15));
+        assert_eq!(
+            spec.next_elapse(&context),
+            Some(boot + Duration::seconds(15) + Duration::hours(1))
+        );
+
+        context.unit_active = Some(boot + Duration::seconds(15));
+        context.random = 61_000_000;
+        assert_eq!(
+            spec.next_elapse(&context),
+            Some(boot + Duration::seconds(15) + Duration::hours(1) + Duration::seconds(61))
+        );
+
+        // relative to the last trigger if the unit was started before
+        context.last_trigger = Some(boot + Duration::minutes(20));
+        context.random = 600_000_000 + 1;
+        assert_eq!(
+            spec.next_elapse(&context),
+            Some(boot + Duration::minutes(80) + Duration::microseconds(1))
+        );
+
+        let fixed = TimerSpec {
+            fixed_random_delay: true,
+            ..spec
+        };
+        context.fixed_seed = 2_000_000;
+        assert_eq!(
+            fixed.next_elapse(&context),
+            Some(boot + Duration::minutes(80) + Duration::seconds(2))
+        );
+    }
+
+    #[test]
+    fn test_from_section() {
+        let content = "[Timer]\nOnBootSec=15min\nOnUnitActiveSec=1h 30min\nOnUnitActiveSec=1d\nRandomizedDelaySec=5min\nPersistent=yes\n";
+        let mut parser =
+            UnitParser::new(content, false, "foo.timer", Path::new("/etc/foo.timer")).unwrap();
+        let section = parser.next().unwrap().unwrap();
+        let section = TimerSection::__parse_section(section).unwrap().unwrap();
+
+        let spec = TimerSpec::from(&section);
+        assert_eq!(
+            spec,
+            TimerSpec {
+                on_boot: vec![Duration::minutes(15)],
+                on_unit_active: vec![Duration::minutes(90), Duration::days(1)],
+                randomized_delay: Duration::minutes(5),
+                persistent: true,
+                ..Default::default()
+            }
+        );
+    }
+}
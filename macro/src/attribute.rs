@@ -1,23 +1,18 @@
 use proc_macro2::TokenStream;
 use quote::ToTokens;
-use syn::{Attribute, Error, Expr, Field, LitStr, Token, Type};
+use syn::{Attribute, DeriveInput, Error, Expr, Field, Fields, LitStr, Path, Token, Type, Variant};
 
-use crate::type_transform::{is_option, is_vec};
+use crate::{
+    rename::RenameRule,
+    type_transform::{is_option, is_vec},
+};
 
+#[derive(Default)]
 pub(crate) struct SectionAttributes {
     pub(crate) default: bool,
     pub(crate) key: Option<TokenStream>,
     pub(crate) must: bool,
-}
-
-impl Default for SectionAttributes {
-    fn default() -> Self {
-        Self {
-            default: false,
-            key: None,
-            must: false,
-        }
-    }
+    pub(crate) multiple: bool,
 }
 
 impl SectionAttributes {
@@ -37,6 +32,9 @@ impl SectionAttributes {
                     } else if nested.path.is_ident("must") {
                         result.must = true;
                         Ok(())
+                    } else if nested.path.is_ident("multiple") {
+                        result.multiple = true;
+                        Ok(())
                     } else {
                         Err(Error::new_spanned(attribute, "Not a valid attribute."))
                     }
@@ -49,8 +47,20 @@ impl SectionAttributes {
                 "`default` and `must` cannot co-exist.",
             ));
         }
+        if result.multiple & (result.default | result.must) {
+            return Err(Error::new_spanned(
+                input,
+                "`multiple` cannot co-exist with `default` or `must`.",
+            ));
+        }
         if let Some(ty) = ty {
-            if (!result.must) & (!result.default) & (!is_option(ty)) {
+            if result.multiple & (!is_vec(ty)) {
+                return Err(Error::new_spanned(
+                    input,
+                    "`multiple` attributed sections should be `Vec`s.",
+                ));
+            }
+            if (!result.must) & (!result.default) & (!result.multiple) & (!is_option(ty)) {
                 return Err(Error::new_spanned(
                     input,
                     "Optional fields should be `Option`s.",
@@ -61,24 +71,20 @@ impl SectionAttributes {
     }
 }
 
+#[derive(Default)]
 pub(crate) struct EntryAttributes {
     pub(crate) default: Option<Expr>,
     pub(crate) key: Option<TokenStream>,
     pub(crate) multiple: bool,
     pub(crate) must: bool,
     pub(crate) subdir: Option<TokenStream>,
-}
-
-impl Default for EntryAttributes {
-    fn default() -> Self {
-        Self {
-            default: None,
-            key: None,
-            multiple: false,
-            must: false,
-            subdir: None,
-        }
-    }
+    pub(crate) separator: Option<LitStr>,
+    pub(crate) relax: bool,
+    pub(crate) parse_with: Option<Path>,
+    pub(crate) validate: Option<Path>,
+    pub(crate) aliases: Vec<LitStr>,
+    pub(crate) deprecated: Vec<LitStr>,
+    pub(crate) flatten: bool,
 }
 
 impl EntryAttributes {
@@ -109,12 +115,64 @@ impl EntryAttributes {
                         let value: LitStr = nested.input.parse()?;
                         result.subdir = Some(value.into_token_stream());
                         Ok(())
+                    } else if nested.path.is_ident("separator") {
+                        nested.input.parse::<Token![=]>()?;
+                        let value: LitStr = nested.input.parse()?;
+                        result.separator = Some(value);
+                        Ok(())
+                    } else if nested.path.is_ident("relax") {
+                        result.relax = true;
+                        Ok(())
+                    } else if nested.path.is_ident("parse_with") {
+                        nested.input.parse::<Token![=]>()?;
+                        let value: Path = nested.input.parse()?;
+                        result.parse_with = Some(value);
+                        Ok(())
+                    } else if nested.path.is_ident("validate") {
+                        nested.input.parse::<Token![=]>()?;
+                        let value: Path = nested.input.parse()?;
+                        result.validate = Some(value);
+                        Ok(())
+                    } else if nested.path.is_ident("alias") {
+                        nested.input.parse::<Token![=]>()?;
+                        let value: LitStr = nested.input.parse()?;
+                        result.aliases.push(value);
+                        Ok(())
+                    } else if nested.path.is_ident("deprecated") {
+                        nested.input.parse::<Token![=]>()?;
+                        let value: LitStr = nested.input.parse()?;
+                        result.deprecated.push(value);
+                        Ok(())
+                    } else if nested.path.is_ident("flatten") {
+                        result.flatten = true;
+                        Ok(())
                     } else {
                         Err(Error::new_spanned(attribute, "Not a valid attribute."))
                     }
                 })?;
             }
         }
+        // the entries belong to the flattened section
+        if result.flatten {
+            let others = result.default.is_some()
+                | result.key.is_some()
+                | result.multiple
+                | result.must
+                | result.subdir.is_some()
+                | result.separator.is_some()
+                | result.relax
+                | result.parse_with.is_some()
+                | result.validate.is_some()
+                | !result.aliases.is_empty()
+                | !result.deprecated.is_empty();
+            if others {
+                return Err(Error::new_spanned(
+                    input,
+                    "`flatten` cannot co-exist with other attributes.",
+                ));
+            }
+            return Ok(result);
+        }
         if result.must & result.default.is_some() {
             return Err(Error::new_spanned(
                 input,
@@ -133,9 +191,21 @@ impl EntryAttributes {
                 "`subdir` attributed fields must be `multiple`.",
             ));
         }
+        if (!result.multiple) & (result.separator.is_some() | result.relax) {
+            return Err(Error::new_spanned(
+                input,
+                "`separator` and `relax` attributed fields must be `multiple`.",
+            ));
+        }
+        if result
+            .separator
+            .as_ref()
+            .is_some_and(|x| x.value().is_empty())
+        {
+            return Err(Error::new_spanned(input, "`separator` cannot be empty."));
+        }
         if let Some(ty) = ty {
-            if (!result.must) & (!result.default.is_some()) & (!result.multiple) & (!is_option(ty))
-            {
+            if (!result.must) & result.default.is_none() & (!result.multiple) & (!is_option(ty)) {
                 return Err(Error::new_spanned(
                     input,
                     "Optional fields should be `Option`s.",
@@ -152,19 +222,15 @@ impl EntryAttributes {
     }
 }
 
+#[derive(Default)]
 pub(crate) struct UnitAttributes {
     pub(crate) suffix: Option<LitStr>,
-}
-
-impl Default for UnitAttributes {
-    fn default() -> Self {
-        Self { suffix: None }
-    }
+    pub(crate) include: bool,
 }
 
 impl UnitAttributes {
     // pass in type to do type check, or pass in None to prevent errors from showing up multiple times
-    pub(crate) fn parse_vec(input: &Vec<Attribute>) -> syn::Result<Self> {
+    pub(crate) fn parse_vec(input: &[Attribute]) -> syn::Result<Self> {
         let mut result = UnitAttributes::default();
         for attribute in input.iter() {
             if attribute.path().is_ident("unit") {
@@ -174,6 +240,9 @@ impl UnitAttributes {
                         let value: LitStr = nested.input.parse()?;
                         result.suffix = Some(value);
                         Ok(())
+                    } else if nested.path.is_ident("include") {
+                        result.include = true;
+                        Ok(())
                     } else {
                         Err(Error::new_spanned(attribute, "Not a valid attribute."))
                     }
@@ -183,3 +252,147 @@ impl UnitAttributes {
         Ok(result)
     }
 }
+
+#[derive(Default)]
+pub(crate) struct EntryValueAttributes {
+    pub(crate) rename_all: Option<RenameRule>,
+    pub(crate) case_insensitive: bool,
+    pub(crate) delimiter: Option<LitStr>,
+    pub(crate) validate: Option<Path>,
+    pub(crate) display: bool,
+}
+
+impl EntryValueAttributes {
+    // `enum` tells which attributes are allowed, those of enums or those of structs
+    pub(crate) fn parse_vec(input: &DeriveInput, r#enum: bool) -> syn::Result<Self> {
+        let mut result = EntryValueAttributes::default();
+        for attribute in input.attrs.iter() {
+            if attribute.path().is_ident("entry_value") {
+                attribute.parse_nested_meta(|nested| {
+                    if nested.path.is_ident("rename_all") {
+                        nested.input.parse::<Token![=]>()?;
+                        let value: LitStr = nested.input.parse()?;
+                        result.rename_all = Some(RenameRule::parse(&value)?);
+                        Ok(())
+                    } else if nested.path.is_ident("case_insensitive") {
+                        result.case_insensitive = true;
+                        Ok(())
+                    } else if nested.path.is_ident("delimiter") {
+                        nested.input.parse::<Token![=]>()?;
+                        let value: LitStr = nested.input.parse()?;
+                        result.delimiter = Some(value);
+                        Ok(())
+                    } else if nested.path.is_ident("validate") {
+                        nested.input.parse::<Token![=]>()?;
+                        let value: Path = nested.input.parse()?;
+                        result.validate = Some(value);
+                        Ok(())
+                    } else if nested.path.is_ident("display") {
+                        result.display = true;
+                        Ok(())
+                    } else {
+                        Err(Error::new_spanned(attribute, "Not a valid attribute."))
+                    }
+                })?;
+            }
+        }
+        if r#enum & (result.delimiter.is_some() | result.validate.is_some() | result.display) {
+            return Err(Error::new_spanned(
+                input,
+                "`delimiter`, `validate` and `display` are only supported on structs.",
+            ));
+        }
+        if (!r#enum) & (result.rename_all.is_some() | result.case_insensitive) {
+            return Err(Error::new_spanned(
+                input,
+                "`rename_all` and `case_insensitive` are only supported on enums.",
+            ));
+        }
+        if result
+            .delimiter
+            .as_ref()
+            .is_some_and(|x| x.value().is_empty())
+        {
+            return Err(Error::new_spanned(input, "`delimiter` cannot be empty."));
+        }
+        Ok(result)
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct VariantAttributes {
+    pub(crate) rename: Option<LitStr>,
+    pub(crate) aliases: Vec<LitStr>,
+    pub(crate) prefix: Option<LitStr>,
+    pub(crate) other: bool,
+}
+
+impl VariantAttributes {
+    pub(crate) fn parse_vec(input: &Variant) -> syn::Result<Self> {
+        let mut result = VariantAttributes::default();
+        for attribute in input.attrs.iter() {
+            if attribute.path().is_ident("entry_value") {
+                attribute.parse_nested_meta(|nested| {
+                    if nested.path.is_ident("rename") {
+                        nested.input.parse::<Token![=]>()?;
+                        let value: LitStr = nested.input.parse()?;
+                        result.rename = Some(value);
+                        Ok(())
+                    } else if nested.path.is_ident("alias") {
+                        nested.input.parse::<Token![=]>()?;
+                        let value: LitStr = nested.input.parse()?;
+                        result.aliases.push(value);
+                        Ok(())
+                    } else if nested.path.is_ident("prefix") {
+                        nested.input.parse::<Token![=]>()?;
+                        let value: LitStr = nested.input.parse()?;
+                        result.prefix = Some(value);
+                        Ok(())
+                    } else if nested.path.is_ident("other") {
+                        result.other = true;
+                        Ok(())
+                    } else {
+                        Err(Error::new_spanned(attribute, "Not a valid attribute."))
+                    }
+                })?;
+            }
+        }
+        let named = result.rename.is_some() | !result.aliases.is_empty();
+        if result.prefix.is_some() & result.other {
+            return Err(Error::new_spanned(
+                input,
+                "`prefix` and `other` cannot co-exist.",
+            ));
+        }
+        if (result.prefix.is_some() | result.other) & named {
+            return Err(Error::new_spanned(
+                input,
+                "`prefix` and `other` variants cannot have a `rename` or `alias`.",
+            ));
+        }
+        if result.prefix.as_ref().is_some_and(|x| x.value().is_empty()) {
+            return Err(Error::new_spanned(input, "`prefix` cannot be empty."));
+        }
+        match &input.fields {
+            Fields::Unit if result.prefix.is_some() => Err(Error::new_spanned(
+                input,
+                "`prefix` attributed variants should have a single field.",
+            )),
+            Fields::Unnamed(fields) if fields.unnamed.len() != 1 => Err(Error::new_spanned(
+                input,
+                "Variants with data should have a single field.",
+            )),
+            Fields::Unnamed(_) if result.prefix.is_none() & !result.other => {
+                Err(Error::new_spanned(
+                    input,
+                    "Variants with data should be `prefix` or `other` attributed.",
+                ))
+            }
+            Fields::Named(_) => Err(Error::new_spanned(
+                input,
+                "Variants with named fields are not supported.",
+            )),
+            _ => Ok(result),
+        }
+    }
+}
@@ -1,26 +1,39 @@
 use proc_macro2::TokenStream;
-use quote::{quote, ToTokens};
-use syn::{Data, DeriveInput, Error, Field, Result};
+use quote::{format_ident, quote, ToTokens};
+use syn::{Data, DeriveInput, Error, Field, Ident, Result, Type};
 
 use crate::{
     attribute::EntryAttributes,
     transform_default::transform_default,
     type_transform::{extract_type_from_option, extract_type_from_vec},
+    value::{gen_enum_derives, gen_struct_derives},
 };
 
 pub(crate) fn gen_entry_ensure(field: &Field) -> Result<TokenStream> {
     let mut ty = &field.ty;
     let attribute = EntryAttributes::parse_vec(field, None)?;
+    if attribute.flatten {
+        return Ok(quote! {
+            {
+                fn assert_impl<'__unit, T: unit_parser::internal::UnitSection<'__unit>>() {}
+                assert_impl::<#ty>();
+            }
+        });
+    }
+    // the type is up to the function
+    if attribute.parse_with.is_some() {
+        return Ok(quote! {});
+    }
     if attribute.multiple {
         ty = extract_type_from_vec(ty)?;
     } else if (!attribute.must) & (attribute.default.is_none()) {
         ty = extract_type_from_option(ty)?;
     }
     Ok(quote! {
-        const _: fn() = || {
-            fn assert_impl<T: UnitEntry>() {}
+        {
+            fn assert_impl<'__unit, T: unit_parser::internal::UnitEntryRef<'__unit>>() {}
             assert_impl::<#ty>();
-        };
+        }
     })
 }
 
@@ -30,12 +43,20 @@ pub(crate) fn gen_entry_init(field: &Field) -> Result<TokenStream> {
         "Tuple structs are not supported.",
     ))?;
     let attributes = EntryAttributes::parse_vec(field, None)?;
+    let reset = reset_ident(name);
+    // the entries of a flattened section are collected first
+    if attributes.flatten {
+        return Ok(quote! {
+            let mut #name = Vec::new();
+        });
+    }
     Ok(match attributes.multiple {
         false => quote! {
             let mut #name = None;
         },
         true => quote! {
             let mut #name = Vec::new();
+            let mut #reset = false;
         },
     })
 }
@@ -47,87 +68,187 @@ pub(crate) fn gen_entry_parse(field: &Field) -> Result<TokenStream> {
     ))?;
     let ty = &field.ty;
     let attributes = EntryAttributes::parse_vec(field, Some(ty))?;
+    if attributes.flatten {
+        return Ok(quote! {
+            _ if <#ty as unit_parser::internal::UnitSection<'_>>::__has_key(__pair.0) => {
+                #name.push(__pair);
+            }
+        });
+    }
     let key = attributes
         .key
+        .clone()
         .unwrap_or((format!("{}", name)).into_token_stream());
 
+    let value = gen_entry_value(&attributes, quote! { __pair.1.clone() });
     let result = match (
-        attributes.default,
+        &attributes.default,
         attributes.multiple,
-        attributes.subdir,
+        &attributes.subdir,
         attributes.must,
     ) {
         // unreachable
         (Some(_), _, _, true) | (_, true, _, true) | (_, false, Some(_), _) => unreachable!(),
-        // add to Vec
-        (_, true, None, _) => {
+        // add to Vec, subdirs are collected once the whole unit is loaded
+        (_, true, _, _) => {
+            let push = gen_entry_push(name, extract_type_from_vec(ty)?, &attributes);
+            let reset = reset_ident(name);
             quote! {
-                #key => {
-                    if __pair.1.as_str().is_empty() {
-                        #name.clear();
-                        continue;
-                    }
-                    for __part in __pair.1.split_ascii_whitespace(){
-                        match unit_parser::internal::UnitEntry::parse_from_str(__part){
-                            Ok(__inner) => {
-                                #name.push(__inner);
-                            }
-                            Err(_) => {
-                                log::warn!("Failed to parse {} for key {}, ignoring.", __pair.0, __pair.1);
-                            }
-                        }
-                    }
-                }
-            }
-        }
-        // add to Vec, as well as subdirs
-        (_, true, Some(subdir), _) => {
-            quote! {
-                #key => {
-                    if __pair.1.as_str().is_empty() {
-                        #name.clear();
-                        continue;
-                    }
-                    for __part in __pair.1.split_ascii_whitespace(){
-                        match unit_parser::internal::UnitEntry::parse_from_str(__part){
-                            Ok(__inner) => {
-                                #name.push(__inner);
-                            }
-                            Err(_) => {
-                                log::warn!("Failed to parse {} for key {}, ignoring.", __pair.0, __pair.1);
-                            }
-                        }
-                    }
-                    let __subdirs = __subdir_parser.__parse_subdir(#subdir);
-                    #name.extend_from_slice(&__subdirs);
+                if __pair.1.is_empty() {
+                    #name.clear();
+                    #reset = true;
+                    continue;
                 }
+                #push
             }
         }
         // set as Some if Ok
         (_, false, None, false) => {
             quote! {
-                #key => {
-                    if let Ok(__value) = unit_parser::internal::UnitEntry::parse_from_str(__pair.1.as_str()) {
+                match #value {
+                    Ok(__value) => {
                         #name = Some(__value);
                     }
+                    Err(__error) => {
+                        log::warn!("Failed to parse {} as the value of entry with key {}: {}, ignoring.", __pair.1, __pair.0, __error);
+                    }
                 }
             }
         }
         // throw Error
         (None, false, None, true) => {
             quote! {
-                #key => {
-                    let __value = unit_parser::internal::UnitEntry::parse_from_str(__pair.1.as_str())
-                        .map_err(|_| unit_parser::internal::Error::ValueParsingError { key: #key.to_string(), value: __pair.1.to_string() })?;
-                    #name = Some(__value);
-                }
+                let __value = #value
+                    .map_err(|__error| unit_parser::internal::Error::ValueParsingError { key: __pair.0.to_string(), value: __pair.1.to_string(), source: __error })?;
+                #name = Some(__value);
             }
         }
     };
 
+    // old names are still accepted, with a warning
+    let aliases = &attributes.aliases;
+    let deprecated = &attributes.deprecated;
+    let warn = match deprecated.is_empty() {
+        true => quote! {},
+        false => quote! {
+            if #( __pair.0 == #deprecated )||* {
+                log::warn!("Key {} is deprecated, use {} instead.", __pair.0, #key);
+            }
+        },
+    };
+    let result = quote! {
+        #key #( | #aliases )* #( | #deprecated )* => {
+            #warn
+            #result
+        }
+    };
+
     Ok(result)
 }
 
+// parse with `parse_with` or `UnitEntryRef`, then check with `validate`, into a result with a boxed error
+fn gen_entry_value(attributes: &EntryAttributes, input: TokenStream) -> TokenStream {
+    let parse = match &attributes.parse_with {
+        Some(parse_with) => quote! { #parse_with(&*__input) },
+        None => quote! { unit_parser::internal::UnitEntryRef::parse_from_cow(__input) },
+    };
+    let validate = attributes.validate.as_ref().map(|validate| {
+        quote! {
+            .and_then(|__value| match #validate(&__value) {
+                Ok(()) => Ok(__value),
+                Err(__error) => Err(__error.into()),
+            })
+        }
+    });
+    quote! {
+        {
+            let __input = #input;
+            #parse.map_err(Into::<Box<dyn std::error::Error + Send + Sync>>::into) #validate
+        }
+    }
+}
+
+// split the value into words the way systemd's `extract_first_word` does, and parse each of them
+// unless the type parses whole assignments, see `UnitEntry::SPLIT`
+fn gen_entry_push(name: &Ident, ty: &Type, attributes: &EntryAttributes) -> TokenStream {
+    let separator = attributes
+        .separator
+        .as_ref()
+        .map_or(quote! { unit_parser::internal::WHITESPACE }, |x| {
+            x.into_token_stream()
+        });
+    let relax = attributes.relax;
+    // functions parse words
+    let split = match attributes.parse_with {
+        Some(_) => quote! { true },
+        None => quote! { <#ty as unit_parser::internal::UnitEntryRef<'_>>::SPLIT },
+    };
+    let value = gen_entry_value(attributes, quote! { __pair.1.clone() });
+    let part = gen_entry_value(attributes, quote! { __part.clone() });
+    quote! {
+        if !#split {
+            match #value {
+                Ok(__inner) => {
+                    #name.push(__inner);
+                }
+                Err(__error) => {
+                    log::warn!("Failed to parse {} as the value of entry with key {}: {}, ignoring.", __pair.1, __pair.0, __error);
+                }
+            }
+            continue;
+        }
+        let mut __offset = 0;
+        loop {
+            match unit_parser::internal::extract_word_from(&__pair.1, &mut __offset, #separator, #relax) {
+                Ok(Some(__part)) => match #part {
+                    Ok(__inner) => {
+                        #name.push(__inner);
+                    }
+                    Err(__error) => {
+                        log::warn!("Failed to parse {} as the value of entry with key {}: {}, ignoring.", __part, __pair.0, __error);
+                    }
+                },
+                Ok(None) => break,
+                Err(__error) => {
+                    log::warn!("{} Ignoring the rest of key {}.", __error, __pair.0);
+                    break;
+                }
+            }
+        }
+    }
+}
+
+// whether an empty assignment reset a `multiple` entry, which also clears the values of the unit patched
+fn reset_ident(name: &Ident) -> Ident {
+    format_ident!("__reset_{}", name)
+}
+
+// add the units found in the subdir, skipping those already listed
+pub(crate) fn gen_entry_collect(
+    name: &Ident,
+    subdir: &TokenStream,
+    attributes: &EntryAttributes,
+) -> TokenStream {
+    let value = gen_entry_value(
+        attributes,
+        quote! { std::borrow::Cow::Owned(__unit.clone()) },
+    );
+    quote! {
+        for __unit in __parser.__parse_subdir(#subdir) {
+            match #value {
+                Ok(__value) => {
+                    if !self.#name.contains(&__value) {
+                        self.#name.push(__value);
+                    }
+                }
+                Err(__error) => {
+                    log::warn!("Failed to parse {} from subdir {}: {}, ignoring.", __unit, #subdir, __error);
+                }
+            }
+        }
+    }
+}
+
 pub(crate) fn gen_entry_finalize(field: &Field) -> Result<TokenStream> {
     let name = field.ident.as_ref().ok_or(Error::new_spanned(
         field,
@@ -135,6 +256,13 @@ pub(crate) fn gen_entry_finalize(field: &Field) -> Result<TokenStream> {
     ))?;
     let ty = &field.ty;
     let attributes = EntryAttributes::parse_vec(field, None)?;
+    if attributes.flatten {
+        let key = name.to_string();
+        return Ok(quote! {
+            let #name = <#ty as unit_parser::internal::UnitSection<'_>>::__parse_section(#name.into_iter().map(Ok))?
+                .ok_or(unit_parser::internal::Error::SectionParsingError { key: #key.to_string() })?;
+        });
+    }
     let key = attributes
         .key
         .unwrap_or((format!("{}", name)).into_token_stream());
@@ -168,40 +296,25 @@ pub(crate) fn gen_entry_finalize(field: &Field) -> Result<TokenStream> {
             }
         }
     };
+    // the reset only matters when patching
+    if attributes.multiple {
+        let reset = reset_ident(name);
+        return Ok(quote! {
+            let _ = #reset;
+            #result
+        });
+    }
     Ok(result)
 }
 
 pub(crate) fn gen_entry_derives(input: DeriveInput) -> Result<TokenStream> {
-    if let Data::Enum(inner) = input.data {
-        let ident = &input.ident;
-        let mut match_arms = Vec::new();
-
-        for variant in inner.variants.iter() {
-            let name = &variant.ident;
-            let value = format!("{}", name);
-            // TODO: support for alt-key
-            let result = quote! {
-                #value => Ok(Self::#name)
-            };
-            match_arms.push(result);
-        }
-
-        Ok(quote! {
-            impl unit_parser::internal::UnitEntry for #ident {
-                type Error = ();
-                fn parse_from_str<S: AsRef<str>>(input: S) -> std::result::Result<Self, Self::Error> {
-                    match input.as_ref() {
-                        #( #match_arms ,)*
-                        _ => Err(()),
-                    }
-                }
-            }
-        })
-    } else {
-        Err(Error::new_spanned(
+    match &input.data {
+        Data::Enum(data) => gen_enum_derives(&input, data),
+        Data::Struct(data) => gen_struct_derives(&input, data),
+        Data::Union(_) => Err(Error::new_spanned(
             input,
-            "UnitEntry can only be derived on enum definitions.",
-        ))
+            "UnitEntry can only be derived on enums and structs.",
+        )),
     }
 }
 
@@ -211,15 +324,24 @@ pub(crate) fn gen_entry_patch(field: &Field) -> Result<TokenStream> {
         "Tuple structs are not supported.",
     ))?;
     let attributes = EntryAttributes::parse_vec(field, None)?;
+    if attributes.flatten {
+        let ty = &field.ty;
+        return Ok(quote! {
+            <#ty as unit_parser::internal::UnitSection<'_>>::__patch_section(#name.into_iter().map(Ok), &mut __from.#name)?;
+        });
+    }
 
     let result = match (attributes.must, attributes.multiple, attributes.default) {
         // invalid
         (true, _, Some(_)) | (true, true, _) => unreachable!(),
-        // append
-        // TODO: or should it overwrite?
+        // append, or overwrite after an empty assignment
         (false, true, _) => {
+            let reset = reset_ident(name);
             quote! {
-                __from.#name.extend_from_slice(&#name);
+                if #reset {
+                    __from.#name.clear();
+                }
+                __from.#name.append(&mut #name);
             }
         }
         // set (as is) if not None
@@ -0,0 +1,24 @@
+use proc_macro2::Span;
+use syn::{Error, GenericParam, Generics, Lifetime, LifetimeParam, Result};
+
+// returns the generics to implement the traits with, along with the lifetime of the unit file buffer,
+// which is either the only lifetime of the struct, or a new one if the struct borrows nothing
+pub(crate) fn unit_lifetime(generics: &Generics) -> Result<(Generics, Lifetime)> {
+    let mut lifetimes = generics.lifetimes();
+    match (lifetimes.next(), lifetimes.next()) {
+        (None, _) => {
+            let lifetime = Lifetime::new("'__unit", Span::call_site());
+            let mut generics = generics.clone();
+            generics.params.insert(
+                0,
+                GenericParam::Lifetime(LifetimeParam::new(lifetime.clone())),
+            );
+            Ok((generics, lifetime))
+        }
+        (Some(lifetime), None) => Ok((generics.clone(), lifetime.lifetime.clone())),
+        (Some(_), Some(second)) => Err(Error::new_spanned(
+            second,
+            "At most one lifetime parameter is supported.",
+        )),
+    }
+}
@@ -5,10 +5,13 @@ use unit::gen_unit_derives;
 
 mod attribute;
 mod entry;
+mod generics;
+mod rename;
 mod section;
 mod transform_default;
 mod type_transform;
 mod unit;
+mod value;
 
 #[proc_macro_derive(UnitConfig, attributes(unit, section))]
 pub fn derive_unit_config(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
@@ -28,7 +31,7 @@ pub fn derive_unit_section(input: proc_macro::TokenStream) -> proc_macro::TokenS
         .into()
 }
 
-#[proc_macro_derive(UnitEntry)]
+#[proc_macro_derive(UnitEntry, attributes(entry_value))]
 pub fn derive_unit_entry(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
 
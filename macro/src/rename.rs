@@ -0,0 +1,64 @@
+use syn::{Error, LitStr, Result};
+
+/// The case conventions of `#[entry_value(rename_all = "...")]`, applied to `PascalCase` variant names.
+#[derive(Clone, Copy)]
+pub(crate) enum RenameRule {
+    Lower,
+    Upper,
+    Pascal,
+    Camel,
+    Snake,
+    ScreamingSnake,
+    Kebab,
+    ScreamingKebab,
+}
+
+impl RenameRule {
+    pub(crate) fn parse(input: &LitStr) -> Result<Self> {
+        Ok(match input.value().as_str() {
+            "lowercase" => Self::Lower,
+            "UPPERCASE" => Self::Upper,
+            "PascalCase" => Self::Pascal,
+            "camelCase" => Self::Camel,
+            "snake_case" => Self::Snake,
+            "SCREAMING_SNAKE_CASE" => Self::ScreamingSnake,
+            "kebab-case" => Self::Kebab,
+            "SCREAMING-KEBAB-CASE" => Self::ScreamingKebab,
+            _ => {
+                return Err(Error::new_spanned(
+                    input,
+                    "Unknown rename rule, expected one of `lowercase`, `UPPERCASE`, `PascalCase`, `camelCase`, `snake_case`, `SCREAMING_SNAKE_CASE`, `kebab-case` or `SCREAMING-KEBAB-CASE`.",
+                ))
+            }
+        })
+    }
+
+    pub(crate) fn apply(&self, name: &str) -> String {
+        // words start at upper case letters
+        let separated = |separator: char| {
+            let mut result = String::new();
+            for (i, char) in name.chars().enumerate() {
+                if i > 0 && char.is_uppercase() {
+                    result.push(separator);
+                }
+                result.extend(char.to_lowercase());
+            }
+            result
+        };
+        match self {
+            Self::Lower => name.to_lowercase(),
+            Self::Upper => name.to_uppercase(),
+            Self::Pascal => name.to_string(),
+            Self::Camel => {
+                let mut chars = name.chars();
+                chars
+                    .next()
+                    .map_or(String::new(), |x| x.to_lowercase().chain(chars).collect())
+            }
+            Self::Snake => separated('_'),
+            Self::ScreamingSnake => separated('_').to_uppercase(),
+            Self::Kebab => separated('-'),
+            Self::ScreamingKebab => separated('-').to_uppercase(),
+        }
+    }
+}
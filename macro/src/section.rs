@@ -1,13 +1,15 @@
 use crate::{
-    attribute::SectionAttributes,
+    attribute::{EntryAttributes, SectionAttributes},
     entry::{
-        gen_entry_ensure, gen_entry_finalize, gen_entry_init, gen_entry_parse, gen_entry_patch,
+        gen_entry_collect, gen_entry_ensure, gen_entry_finalize, gen_entry_init, gen_entry_parse,
+        gen_entry_patch,
     },
-    type_transform::extract_type_from_option,
+    generics::unit_lifetime,
+    type_transform::{extract_type_from_option, extract_type_from_vec},
 };
 use proc_macro2::TokenStream;
 use quote::{quote, ToTokens};
-use syn::{Data, DeriveInput, Error, Field, Result};
+use syn::{Data, DeriveInput, Error, Field, Lifetime, Result};
 
 pub fn gen_section_derives(input: DeriveInput) -> Result<TokenStream> {
     let mut entry_ensures = Vec::new();
@@ -16,6 +18,10 @@ pub fn gen_section_derives(input: DeriveInput) -> Result<TokenStream> {
     let mut entry_finalizes = Vec::new();
     let mut entries = Vec::new();
     let mut entry_patches = Vec::new();
+    let mut subdirs = Vec::new();
+    let mut subdir_collects = Vec::new();
+    let mut keys: Vec<TokenStream> = Vec::new();
+    let mut flattens = Vec::new();
 
     if let Data::Struct(data_struct) = &input.data {
         for entry in &data_struct.fields {
@@ -23,13 +29,44 @@ pub fn gen_section_derives(input: DeriveInput) -> Result<TokenStream> {
             entry_inits.push(gen_entry_init(entry)?);
             entry_parsers.push(gen_entry_parse(entry)?);
             entry_finalizes.push(gen_entry_finalize(entry)?);
-            entry_patches.push(gen_entry_patch(&entry)?);
-
+            entry_patches.push(gen_entry_patch(entry)?);
             let ident = entry.ident.as_ref().ok_or(Error::new_spanned(
-                &entry,
+                entry,
                 "An entry must have an explicit name.",
             ))?;
+            let attributes = EntryAttributes::parse_vec(entry, None)?;
             entries.push(ident);
+            if attributes.flatten {
+                let ty = &entry.ty;
+                subdir_collects.push(quote! {
+                    unit_parser::internal::UnitSection::__collect_subdirs(&mut self.#ident, __parser);
+                });
+                flattens.push(ty);
+                continue;
+            }
+            let key = attributes
+                .key
+                .clone()
+                .unwrap_or(format!("{}", ident).into_token_stream());
+            for key in std::iter::once(key).chain(
+                attributes
+                    .aliases
+                    .iter()
+                    .chain(attributes.deprecated.iter())
+                    .map(ToTokens::to_token_stream),
+            ) {
+                if keys.iter().any(|x| x.to_string() == key.to_string()) {
+                    return Err(Error::new_spanned(
+                        entry,
+                        format!("Key {} is used by multiple entries.", key),
+                    ));
+                }
+                keys.push(key);
+            }
+            if let Some(subdir) = &attributes.subdir {
+                subdir_collects.push(gen_entry_collect(ident, subdir, &attributes));
+                subdirs.push(subdir.clone());
+            }
         }
     } else {
         return Err(Error::new_spanned(
@@ -38,12 +75,19 @@ pub fn gen_section_derives(input: DeriveInput) -> Result<TokenStream> {
         ));
     }
 
+    let own_keys = match keys.is_empty() {
+        true => quote! { false },
+        false => quote! { matches!(__key, #( #keys )|*) },
+    };
+
     let ident = &input.ident;
+    let (generics, lifetime) = unit_lifetime(&input.generics)?;
+    let (impl_generics, _, where_clause) = generics.split_for_impl();
+    let (_, ty_generics, _) = input.generics.split_for_impl();
 
     let result = quote! {
-        impl unit_parser::internal::UnitSection for #ident {
-            fn __parse_section(__source: unit_parser::internal::SectionParser) -> unit_parser::internal::Result<Option<Self>> {
-                let __subdir_parser = __source.__subdir_parser();
+        impl #impl_generics unit_parser::internal::UnitSection<#lifetime> for #ident #ty_generics #where_clause {
+            fn __parse_section<__I: Iterator<Item = unit_parser::internal::Result<(&#lifetime str, std::borrow::Cow<#lifetime, str>)>>>(__source: __I) -> unit_parser::internal::Result<Option<Self>> {
                 # ( #entry_ensures )*
                 # ( #entry_inits )*
                 for __entry in __source {
@@ -61,8 +105,7 @@ pub fn gen_section_derives(input: DeriveInput) -> Result<TokenStream> {
                 }))
             }
 
-            fn __patch_section(__source: unit_parser::internal::SectionParser, __from: &mut Self) -> unit_parser::internal::Result<()> {
-                let __subdir_parser = __source.__subdir_parser();
+            fn __patch_section<__I: Iterator<Item = unit_parser::internal::Result<(&#lifetime str, std::borrow::Cow<#lifetime, str>)>>>(__source: __I, __from: &mut Self) -> unit_parser::internal::Result<()> {
                 # ( #entry_ensures )*
                 # ( #entry_inits )*
                 for __entry in __source {
@@ -77,10 +120,53 @@ pub fn gen_section_derives(input: DeriveInput) -> Result<TokenStream> {
                 #( #entry_patches )*
                 Ok(())
             }
+
+            const __KEYS: unit_parser::internal::SectionKeys = unit_parser::internal::SectionKeys {
+                keys: &[ #( #keys ),* ],
+                flattened: &[ #( &<#flattens as unit_parser::internal::UnitSection<#lifetime>>::__KEYS ),* ],
+            };
+
+            fn __has_key(__key: &str) -> bool {
+                #own_keys #( || <#flattens as unit_parser::internal::UnitSection<'_>>::__has_key(__key) )*
+            }
+
+            fn __subdirs() -> Vec<&'static str> {
+                let mut __subdirs = vec![ #( #subdirs ),* ];
+                #( __subdirs.extend(<#flattens as unit_parser::internal::UnitSection<'_>>::__subdirs()); )*
+                __subdirs
+            }
+
+            fn __collect_subdirs(&mut self, __parser: &unit_parser::internal::SubdirParser) {
+                #( #subdir_collects )*
+            }
         }
     };
 
-    Ok(result)
+    // a key shared with a flattened section would silently go to whichever field comes first, sections
+    // generic over types are only checked when used
+    let check = match flattens.is_empty()
+        || input.generics.type_params().next().is_some()
+        || input.generics.const_params().next().is_some()
+    {
+        true => quote! {},
+        false => {
+            let lifetimes = input.generics.lifetimes().map(|_| quote! { 'static });
+            let message = format!(
+                "A key of {} is also accepted by one of its flattened sections.",
+                ident
+            );
+            quote! {
+                const _: () = if <#ident < #( #lifetimes ),* > as unit_parser::internal::UnitSection<'static>>::__KEYS.duplicate().is_some() {
+                    panic!(#message);
+                };
+            }
+        }
+    };
+
+    Ok(quote! {
+        #result
+        #check
+    })
 }
 
 pub(crate) fn gen_section_init(field: &Field) -> Result<TokenStream> {
@@ -88,8 +174,14 @@ pub(crate) fn gen_section_init(field: &Field) -> Result<TokenStream> {
         field,
         "Tuple structs are not supported.",
     ))?;
-    Ok(quote! {
-        let mut #name = None;
+    let attributes = SectionAttributes::parse_vec(field, None)?;
+    Ok(match attributes.multiple {
+        false => quote! {
+            let mut #name = None;
+        },
+        true => quote! {
+            let mut #name = Vec::new();
+        },
     })
 }
 
@@ -104,6 +196,24 @@ pub(crate) fn gen_section_parse(field: &Field) -> Result<(TokenStream, TokenStre
         .key
         .unwrap_or((format!("{}", name)).into_token_stream());
 
+    // every occurrence is a new section, also in drop-ins
+    if attributes.multiple {
+        let push = quote! {
+            #key => {
+                match unit_parser::internal::UnitSection::__parse_section(__section) {
+                    Ok(Some(__value)) => {
+                        #name.push(__value);
+                    }
+                    Ok(None) => {}
+                    Err(__error) => {
+                        log::warn!("Failed to parse section {}, skipping: {}", #key, __error);
+                    }
+                }
+            }
+        };
+        return Ok((push.clone(), push));
+    }
+
     let result = match (attributes.default, attributes.must) {
         // invalid
         (true, true) => unreachable!(),
@@ -111,24 +221,27 @@ pub(crate) fn gen_section_parse(field: &Field) -> Result<(TokenStream, TokenStre
         (true, false) => (
             quote! {
                 #key => {
-                    const _: fn() = || {
+                    {
                         fn assert_impl<T: Default>() {}
                         assert_impl::<#ty>();
-                    };
-                    if let Ok(__value) = unit_parser::internal::UnitSection::__parse_section(__section) {
-                        if __value.is_some() {
-                            #name = __value;
+                    }
+                    match unit_parser::internal::UnitSection::__parse_section(__section) {
+                        Ok(__value) => {
+                            if __value.is_some() {
+                                #name = __value;
+                            }
+                        }
+                        Err(__error) => {
+                            log::warn!("Failed to parse section {}: {}", #key, __error);
                         }
-                    } else {
-                        log::warn!("Failed to parse section {}.", #key);
                     }
                 }
             },
             quote! {
                 #key => {
                     let __section_partial = &mut __from.#name;
-                    if let Err(_)= unit_parser::internal::UnitSection::__patch_section(__section, __section_partial) {
-                        log::warn!("Failed to parse section {}, skipping.", #key)
+                    if let Err(__error) = unit_parser::internal::UnitSection::__patch_section(__section, __section_partial) {
+                        log::warn!("Failed to parse section {}, skipping: {}", #key, __error)
                     }
                 }
             },
@@ -137,22 +250,30 @@ pub(crate) fn gen_section_parse(field: &Field) -> Result<(TokenStream, TokenStre
         (false, false) => (
             quote! {
                 #key => {
-                    if let Ok(__value) = unit_parser::internal::UnitSection::__parse_section(__section) {
-                        #name = __value;
-                    } else {
-                        log::warn!("Failed to parse section {}, skipping.", #key);
+                    match unit_parser::internal::UnitSection::__parse_section(__section) {
+                        Ok(__value) => {
+                            #name = __value;
+                        }
+                        Err(__error) => {
+                            log::warn!("Failed to parse section {}, skipping: {}", #key, __error);
+                        }
                     }
                 }
             },
             quote! {
                 #key => {
                     if let Some(__section_partial) = &mut __from.#name {
-                        if let Err(_) = unit_parser::internal::UnitSection::__patch_section(__section, __section_partial) {
-                            log::warn!("Failed to patch section {}, skipping.", #key);
+                        if let Err(__error) = unit_parser::internal::UnitSection::__patch_section(__section, __section_partial) {
+                            log::warn!("Failed to patch section {}, skipping: {}", #key, __error);
                         }
                     } else {
-                        if let Ok(__inner) = unit_parser::internal::UnitSection::__parse_section(__section) {
-                            __from.#name = __inner;
+                        match unit_parser::internal::UnitSection::__parse_section(__section) {
+                            Ok(__inner) => {
+                                __from.#name = __inner;
+                            }
+                            Err(__error) => {
+                                log::warn!("Failed to parse section {}, skipping: {}", #key, __error);
+                            }
                         }
                     }
                }
@@ -170,8 +291,8 @@ pub(crate) fn gen_section_parse(field: &Field) -> Result<(TokenStream, TokenStre
             quote! {
                 #key => {
                     let __section_partial = &mut __from.#name;
-                    unit_parser::internal::UnitSection::__patch_section(__section, __section_partial)
-                        .map_err(|_| unit_parser::internal::Error::SectionParsingError{ key: #key.to_string() })?;                }
+                    unit_parser::internal::UnitSection::__patch_section(__section, __section_partial)?;
+                }
             },
         ),
     };
@@ -182,14 +303,55 @@ pub(crate) fn gen_section_parse(field: &Field) -> Result<(TokenStream, TokenStre
 pub(crate) fn gen_section_ensure(field: &Field) -> Result<TokenStream> {
     let mut ty = &field.ty;
     let attribute = SectionAttributes::parse_vec(field, None)?;
-    if (!attribute.must) & (!attribute.default) {
+    if attribute.multiple {
+        ty = extract_type_from_vec(ty)?;
+    } else if (!attribute.must) & (!attribute.default) {
         ty = extract_type_from_option(ty)?;
     }
     Ok(quote! {
-        const _: fn() = || {
-            fn assert_impl<T: UnitSection>() {}
+        {
+            fn assert_impl<'__unit, T: unit_parser::internal::UnitSection<'__unit>>() {}
             assert_impl::<#ty>();
-        };
+        }
+    })
+}
+
+pub(crate) fn gen_section_subdirs(field: &Field, lifetime: &Lifetime) -> Result<TokenStream> {
+    let mut ty = &field.ty;
+    let attribute = SectionAttributes::parse_vec(field, None)?;
+    if attribute.multiple {
+        ty = extract_type_from_vec(ty)?;
+    } else if (!attribute.must) & (!attribute.default) {
+        ty = extract_type_from_option(ty)?;
+    }
+    Ok(quote! {
+        __subdirs.extend(<#ty as unit_parser::internal::UnitSection<#lifetime>>::__subdirs());
+    })
+}
+
+pub(crate) fn gen_section_collect(field: &Field) -> Result<TokenStream> {
+    let name = field.ident.as_ref().ok_or(Error::new_spanned(
+        field,
+        "Tuple structs are not supported.",
+    ))?;
+    let attribute = SectionAttributes::parse_vec(field, None)?;
+    if attribute.multiple {
+        return Ok(quote! {
+            for __section in self.#name.iter_mut() {
+                unit_parser::internal::UnitSection::__collect_subdirs(__section, __parser);
+            }
+        });
+    }
+    Ok(match attribute.must | attribute.default {
+        true => quote! {
+            unit_parser::internal::UnitSection::__collect_subdirs(&mut self.#name, __parser);
+        },
+        // sections missing from the unit file are not created just for their subdirs
+        false => quote! {
+            if let Some(__section) = &mut self.#name {
+                unit_parser::internal::UnitSection::__collect_subdirs(__section, __parser);
+            }
+        },
     })
 }
 
@@ -204,6 +366,10 @@ pub(crate) fn gen_section_finalize(field: &Field) -> Result<TokenStream> {
         .key
         .unwrap_or((format!("{}", name)).into_token_stream());
 
+    if attributes.multiple {
+        return Ok(quote! {});
+    }
+
     let result = match (attributes.default, attributes.must) {
         (true, true) => unreachable!(),
         // fallback to default
@@ -233,6 +399,12 @@ pub(crate) fn gen_section_patches(field: &Field) -> Result<TokenStream> {
         "Tuple structs are not supported.",
     ))?;
     let attributes = SectionAttributes::parse_vec(field, None)?;
+    // sections of drop-ins come after those of the unit file
+    if attributes.multiple {
+        return Ok(quote! {
+            __from.#name.append(&mut #name);
+        });
+    }
 
     let result = match (attributes.must, attributes.default) {
         // invalid
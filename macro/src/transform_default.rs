@@ -1,5 +1,5 @@
 use proc_macro2::TokenStream;
-use quote::ToTokens;
+use quote::{quote, ToTokens};
 use syn::{Error, Expr, Lit, Type};
 
 pub(crate) fn transform_default(ty: &Type, default: &Expr) -> Result<TokenStream, Error> {
@@ -19,6 +19,14 @@ pub(crate) fn transform_default(ty: &Type, default: &Expr) -> Result<TokenStream
                 }
             }
         }
+        // wrap in `Cow::Borrowed` if ty is Cow
+        if path.ident == "Cow" {
+            if let Expr::Lit(expr) = default {
+                if let Lit::Str(_) = &expr.lit {
+                    return Ok(quote! { std::borrow::Cow::Borrowed(#default) });
+                }
+            }
+        }
     }
     Ok(default.into_token_stream())
 }
@@ -44,6 +44,27 @@ pub(crate) fn is_vec(ty: &Type) -> bool {
     }
 }
 
+// paths have no `Display` of their own and are formatted with `.display()`
+pub(crate) fn is_path(ty: &Type) -> bool {
+    match ty {
+        Type::Path(typepath) if typepath.qself.is_none() => {
+            let idents_of_path = typepath
+                .path
+                .segments
+                .iter()
+                .fold(String::new(), |mut acc, v| {
+                    acc.push_str(&v.ident.to_string());
+                    acc.push(':');
+                    acc
+                });
+            vec!["PathBuf:", "std:path:PathBuf:"]
+                .into_iter()
+                .any(|s| idents_of_path == s)
+        }
+        _ => false,
+    }
+}
+
 // credits: https://stackoverflow.com/a/56264023
 pub(crate) fn extract_type_from_option(ty: &syn::Type) -> Result<&Type, Error> {
     fn extract_type_path(ty: &syn::Type) -> Result<&Path, Error> {
@@ -56,18 +77,14 @@ pub(crate) fn extract_type_from_option(ty: &syn::Type) -> Result<&Type, Error> {
     // TODO store (with lazy static) the vec of string
     // TODO maybe optimization, reverse the order of segments
     fn extract_option_segment(path: &Path) -> Result<&PathSegment, Error> {
-        let idents_of_path = path
-            .segments
-            .iter()
-            .into_iter()
-            .fold(String::new(), |mut acc, v| {
-                acc.push_str(&v.ident.to_string());
-                acc.push('|');
-                acc
-            });
+        let idents_of_path = path.segments.iter().fold(String::new(), |mut acc, v| {
+            acc.push_str(&v.ident.to_string());
+            acc.push('|');
+            acc
+        });
         vec!["Option|", "std|option|Option|", "core|option|Option|"]
             .into_iter()
-            .find(|s| &idents_of_path == *s)
+            .find(|s| idents_of_path == *s)
             .and_then(|_| path.segments.last())
             .ok_or(Error::new_spanned(path, "Expected `Option<T>.`"))
     }
@@ -102,18 +119,14 @@ pub(crate) fn extract_type_from_vec(ty: &syn::Type) -> Result<&Type, Error> {
     // TODO store (with lazy static) the vec of string
     // TODO maybe optimization, reverse the order of segments
     fn extract_vec_segment(path: &Path) -> Result<&PathSegment, Error> {
-        let idents_of_path = path
-            .segments
-            .iter()
-            .into_iter()
-            .fold(String::new(), |mut acc, v| {
-                acc.push_str(&v.ident.to_string());
-                acc.push('|');
-                acc
-            });
+        let idents_of_path = path.segments.iter().fold(String::new(), |mut acc, v| {
+            acc.push_str(&v.ident.to_string());
+            acc.push('|');
+            acc
+        });
         vec!["Vec|", "std|vec|Vec|", "alloc|vec|Vec|"]
             .into_iter()
-            .find(|s| &idents_of_path == *s)
+            .find(|s| idents_of_path == *s)
             .and_then(|_| path.segments.last())
             .ok_or(Error::new_spanned(path, "Expected `Vec<T>.`"))
     }
@@ -1,8 +1,9 @@
 use crate::{
     attribute::UnitAttributes,
+    generics::unit_lifetime,
     section::{
-        gen_section_ensure, gen_section_finalize, gen_section_init, gen_section_parse,
-        gen_section_patches,
+        gen_section_collect, gen_section_ensure, gen_section_finalize, gen_section_init,
+        gen_section_parse, gen_section_patches, gen_section_subdirs,
     },
 };
 use proc_macro2::TokenStream;
@@ -12,22 +13,27 @@ use syn::{Data, DeriveInput, Error};
 // ensure each section derives `UnitSection`
 pub fn gen_unit_derives(input: DeriveInput) -> syn::Result<TokenStream> {
     let attributes = UnitAttributes::parse_vec(&input.attrs)?;
+    let (generics, lifetime) = unit_lifetime(&input.generics)?;
     let mut sections = Vec::new();
     let mut section_ensures = Vec::new();
     let mut section_inits = Vec::new();
     let mut section_parsers = Vec::new();
     let mut section_finalizes = Vec::new();
     let mut section_patches = Vec::new();
+    let mut section_subdirs = Vec::new();
+    let mut section_collects = Vec::new();
 
     if let Data::Struct(data_struct) = &input.data {
         for entry in &data_struct.fields {
-            section_ensures.push(gen_section_ensure(&entry)?);
-            section_inits.push(gen_section_init(&entry)?);
-            section_parsers.push(gen_section_parse(&entry)?);
-            section_finalizes.push(gen_section_finalize(&entry)?);
-            section_patches.push(gen_section_patches(&entry)?);
+            section_ensures.push(gen_section_ensure(entry)?);
+            section_inits.push(gen_section_init(entry)?);
+            section_parsers.push(gen_section_parse(entry)?);
+            section_finalizes.push(gen_section_finalize(entry)?);
+            section_patches.push(gen_section_patches(entry)?);
+            section_subdirs.push(gen_section_subdirs(entry, &lifetime)?);
+            section_collects.push(gen_section_collect(entry)?);
             let ident = entry.ident.as_ref().ok_or(Error::new_spanned(
-                &entry,
+                entry,
                 "An entry must have an explicit name.",
             ))?;
             sections.push(ident);
@@ -43,15 +49,19 @@ pub fn gen_unit_derives(input: DeriveInput) -> syn::Result<TokenStream> {
     let patch_parsers = section_parsers.iter().map(|x| &x.1);
 
     let ident = &input.ident;
+    let (impl_generics, _, where_clause) = generics.split_for_impl();
+    let (_, ty_generics, _) = input.generics.split_for_impl();
 
     let suffix = attributes
         .suffix
         .map_or(quote! {""}, |x| x.to_token_stream());
+    let include = attributes.include;
 
     let result = quote! {
-         impl unit_parser::internal::UnitConfig for #ident {
+         impl #impl_generics unit_parser::internal::UnitConfig<#lifetime> for #ident #ty_generics #where_clause {
             const SUFFIX: &'static str = #suffix;
-            fn __parse_unit(__source: unit_parser::internal::UnitParser) -> unit_parser::internal::Result<Self> {
+            const INCLUDE: bool = #include;
+            fn __parse_unit(__source: unit_parser::internal::UnitParser<#lifetime>) -> unit_parser::internal::Result<Self> {
                 #( #section_ensures )*
                 #( #section_inits )*
                 for __section in __source {
@@ -69,7 +79,7 @@ pub fn gen_unit_derives(input: DeriveInput) -> syn::Result<TokenStream> {
                 })
             }
 
-            fn __patch_unit(__source: unit_parser::internal::UnitParser, __from: &mut Self) -> unit_parser::internal::Result<()> {
+            fn __patch_unit(__source: unit_parser::internal::UnitParser<#lifetime>, __from: &mut Self) -> unit_parser::internal::Result<()> {
                 #( #section_inits )*
                 for __section in __source {
                     let __section = __section?;
@@ -83,6 +93,16 @@ pub fn gen_unit_derives(input: DeriveInput) -> syn::Result<TokenStream> {
                 #( #section_patches )*
                 Ok(())
              }
+
+            fn __subdirs() -> Vec<&'static str> {
+                let mut __subdirs = Vec::new();
+                #( #section_subdirs )*
+                __subdirs
+            }
+
+            fn __collect_subdirs(&mut self, __parser: &unit_parser::internal::SubdirParser) {
+                #( #section_collects )*
+            }
         }
     };
 
@@ -0,0 +1,281 @@
+use proc_macro2::TokenStream;
+use quote::{quote, ToTokens};
+use syn::{DataEnum, DataStruct, DeriveInput, Error, Fields, LitStr, Member, Result, Type};
+
+use crate::{
+    attribute::{EntryValueAttributes, VariantAttributes},
+    type_transform::{extract_type_from_option, is_option, is_path},
+};
+
+// unit variants match their (renamed) names, prefixed variants parse the rest of the value
+pub(crate) fn gen_enum_derives(input: &DeriveInput, data: &DataEnum) -> Result<TokenStream> {
+    let ident = &input.ident;
+    let attributes = EntryValueAttributes::parse_vec(input, true)?;
+    let case_insensitive = attributes.case_insensitive;
+    let normalize = |value: &LitStr| match case_insensitive {
+        true => value.value().to_lowercase(),
+        false => value.value(),
+    };
+    let mut branches = Vec::new();
+    let mut prefixes = Vec::new();
+    let mut other = None;
+    let mut seen = Vec::new();
+    // listed in the error of unknown values
+    let mut expected = Vec::new();
+
+    for variant in data.variants.iter() {
+        let name = &variant.ident;
+        let variant_attributes = VariantAttributes::parse_vec(variant)?;
+        let field = match &variant.fields {
+            Fields::Unnamed(fields) => fields.unnamed.first().map(|x| &x.ty),
+            _ => None,
+        };
+
+        // the rest of the value is parsed as the field
+        if let (Some(prefix), Some(ty)) = (variant_attributes.prefix, field) {
+            if prefixes
+                .iter()
+                .any(|(x, _)| normalize(x) == normalize(&prefix))
+            {
+                return Err(Error::new_spanned(
+                    prefix,
+                    "The same prefix is used by multiple variants.",
+                ));
+            }
+            let strip = match case_insensitive {
+                true => quote! {
+                    __input.get(..#prefix.len()).filter(|x| x.eq_ignore_ascii_case(#prefix)).map(|_| &__input[#prefix.len()..])
+                },
+                false => quote! { __input.strip_prefix(#prefix) },
+            };
+            let branch = quote! {
+                if let Some(__rest) = #strip {
+                    return <#ty as unit_parser::internal::UnitEntry>::parse_from_str(__rest)
+                        .map(Self::#name)
+                        .map_err(|__error| unit_parser::internal::Error::InvalidValueError { value: __input.to_string(), source: Box::new(__error) });
+                }
+            };
+            expected.push(format!("{}...", prefix.value()));
+            prefixes.push((prefix, branch));
+            continue;
+        }
+
+        // anything else, as is or parsed as the field
+        if variant_attributes.other {
+            if other.is_some() {
+                return Err(Error::new_spanned(
+                    variant,
+                    "Only one variant can be `other` attributed.",
+                ));
+            }
+            other = Some(match field {
+                Some(ty) => quote! {
+                    <#ty as unit_parser::internal::UnitEntry>::parse_from_str(__input)
+                        .map(Self::#name)
+                        .map_err(|__error| unit_parser::internal::Error::InvalidValueError { value: __input.to_string(), source: Box::new(__error) })
+                },
+                None => quote! { Ok(Self::#name) },
+            });
+            continue;
+        }
+
+        let value = match (variant_attributes.rename, attributes.rename_all) {
+            (Some(rename), _) => rename,
+            (None, Some(rule)) => LitStr::new(&rule.apply(&name.to_string()), name.span()),
+            (None, None) => LitStr::new(&name.to_string(), name.span()),
+        };
+
+        let mut conditions = Vec::new();
+        for value in std::iter::once(&value).chain(variant_attributes.aliases.iter()) {
+            if seen.contains(&normalize(value)) {
+                return Err(Error::new_spanned(
+                    value,
+                    "The same value is accepted by multiple variants.",
+                ));
+            }
+            seen.push(normalize(value));
+            expected.push(value.value());
+            conditions.push(match case_insensitive {
+                true => quote! { __input.eq_ignore_ascii_case(#value) },
+                false => quote! { __input == #value },
+            });
+        }
+        branches.push(quote! {
+            if #( #conditions )||* {
+                return Ok(Self::#name);
+            }
+        });
+    }
+
+    // exact values first, then the longest matching prefix
+    prefixes.sort_by_key(|(prefix, _)| std::cmp::Reverse(prefix.value().len()));
+    let prefixes = prefixes.into_iter().map(|(_, branch)| branch);
+    let expected = expected.join(", ");
+    let other = other.unwrap_or(quote! {
+        Err(unit_parser::internal::Error::UnknownValueError { value: __input.to_string(), expected: #expected.to_string() })
+    });
+
+    Ok(quote! {
+        impl unit_parser::internal::UnitEntry for #ident {
+            type Error = unit_parser::internal::Error;
+            fn parse_from_str<S: AsRef<str>>(input: S) -> std::result::Result<Self, Self::Error> {
+                let __input = input.as_ref();
+                #( #branches )*
+                #( #prefixes )*
+                #other
+            }
+        }
+    })
+}
+
+// formats a field for the `Display` implementation requested with `display`
+fn gen_field_display(ty: &Type, value: TokenStream) -> TokenStream {
+    match is_path(ty) {
+        true => quote! { std::fmt::Display::fmt(&#value.display(), f)?; },
+        false => quote! { std::fmt::Display::fmt(#value, f)?; },
+    }
+}
+
+// newtypes delegate to their field, other structs parse their fields from the parts of the value separated
+// by `delimiter`, in order
+pub(crate) fn gen_struct_derives(input: &DeriveInput, data: &DataStruct) -> Result<TokenStream> {
+    let ident = &input.ident;
+    let attributes = EntryValueAttributes::parse_vec(input, false)?;
+    let fields: Vec<_> = data.fields.iter().collect();
+    let members: Vec<Member> = fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| match &field.ident {
+            Some(ident) => Member::Named(ident.clone()),
+            None => Member::Unnamed(i.into()),
+        })
+        .collect();
+
+    let delimiter = match (attributes.delimiter, &data.fields) {
+        (None, Fields::Unnamed(_)) if fields.len() == 1 => None,
+        (Some(delimiter), Fields::Named(_) | Fields::Unnamed(_)) if !fields.is_empty() => {
+            Some(delimiter)
+        }
+        _ => return Err(Error::new_spanned(
+            input,
+            "UnitEntry can only be derived on newtype structs, or on structs with a `delimiter`.",
+        )),
+    };
+
+    let Some(delimiter) = delimiter else {
+        let ty = &fields[0].ty;
+        let display = attributes.display.then(|| {
+            let format = gen_field_display(ty, quote! { &self.0 });
+            quote! {
+                impl std::fmt::Display for #ident {
+                    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                        #format
+                        Ok(())
+                    }
+                }
+            }
+        });
+        let validate = attributes.validate.map(|validate| {
+            quote! {
+                #validate(&__value).map_err(|__error| unit_parser::internal::Error::InvalidValueError { value: __input.to_string(), source: __error.into() })?;
+            }
+        });
+        return Ok(quote! {
+            impl unit_parser::internal::UnitEntry for #ident {
+                type Error = unit_parser::internal::Error;
+                const SPLIT: bool = <#ty as unit_parser::internal::UnitEntry>::SPLIT;
+                fn parse_from_str<S: AsRef<str>>(input: S) -> std::result::Result<Self, Self::Error> {
+                    let __input = input.as_ref();
+                    let __value = <#ty as unit_parser::internal::UnitEntry>::parse_from_str(__input)
+                        .map_err(|__error| unit_parser::internal::Error::InvalidValueError { value: __input.to_string(), source: Box::new(__error) })?;
+                    #validate
+                    Ok(Self(__value))
+                }
+            }
+
+            #display
+        });
+    };
+
+    // trailing `Option`s may be left out
+    let mut optional = false;
+    let mut parses = Vec::new();
+    let mut formats = Vec::new();
+    for (i, (field, member)) in fields.iter().zip(members.iter()).enumerate() {
+        let name = quote::format_ident!("__field_{}", i);
+        let separator = match i {
+            0 => quote! {},
+            _ => quote! { f.write_str(#delimiter)?; },
+        };
+        if is_option(&field.ty) {
+            let ty = extract_type_from_option(&field.ty)?;
+            optional = true;
+            parses.push(quote! {
+                let #name = match __parts.next() {
+                    Some(__part) => Some(<#ty as unit_parser::internal::UnitEntry>::parse_from_str(__part.trim_matches(|x| unit_parser::internal::WHITESPACE.contains(x))).map_err(|__error| unit_parser::internal::Error::InvalidValueError { value: __input.to_string(), source: Box::new(__error) })?),
+                    None => None,
+                };
+            });
+            let format = gen_field_display(ty, quote! { __value });
+            formats.push(quote! {
+                if let Some(__value) = &self.#member {
+                    #separator
+                    #format
+                }
+            });
+        } else if optional {
+            return Err(Error::new_spanned(
+                field,
+                "Only the trailing fields of a delimited struct can be `Option`s.",
+            ));
+        } else {
+            let ty = &field.ty;
+            let missing = format!("Missing field {}.", member.to_token_stream());
+            parses.push(quote! {
+                let #name = <#ty as unit_parser::internal::UnitEntry>::parse_from_str(
+                    __parts.next().ok_or_else(|| unit_parser::internal::Error::InvalidValueError { value: __input.to_string(), source: #missing.into() })?.trim_matches(|x| unit_parser::internal::WHITESPACE.contains(x))
+                ).map_err(|__error| unit_parser::internal::Error::InvalidValueError { value: __input.to_string(), source: Box::new(__error) })?;
+            });
+            let format = gen_field_display(ty, quote! { &self.#member });
+            formats.push(quote! {
+                #separator
+                #format
+            });
+        }
+    }
+
+    let display = attributes.display.then(|| {
+        quote! {
+            impl std::fmt::Display for #ident {
+                fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                    #( #formats )*
+                    Ok(())
+                }
+            }
+        }
+    });
+    let count = fields.len();
+    let names = (0..count).map(|i| quote::format_ident!("__field_{}", i));
+    let validate = attributes.validate.map(|validate| {
+        quote! {
+            #validate(&__value).map_err(|__error| unit_parser::internal::Error::InvalidValueError { value: __input.to_string(), source: __error.into() })?;
+        }
+    });
+    Ok(quote! {
+        impl unit_parser::internal::UnitEntry for #ident {
+            type Error = unit_parser::internal::Error;
+            // the delimiter may be whitespace, so `multiple` entries take whole assignments
+            const SPLIT: bool = false;
+            fn parse_from_str<S: AsRef<str>>(input: S) -> std::result::Result<Self, Self::Error> {
+                let __input = input.as_ref();
+                let mut __parts = __input.splitn(#count, #delimiter);
+                #( #parses )*
+                let __value = Self { #( #members: #names ),* };
+                #validate
+                Ok(__value)
+            }
+        }
+
+        #display
+    })
+}